@@ -1,15 +1,14 @@
-﻿use poller::{EventContext, Events, Poller};
+﻿use poller::{Events, Poller};
 use std::io::stdin;
-use std::sync::Arc;
 
 type Callback = fn() -> bool;
 
 fn main() -> Result<(), Box<dyn std::error::Error>> {
-    // Create the Poller.
-    let mut poller = Poller::new()?;
+    // Create the Poller with callbacks as the event context.
+    let mut poller = Poller::<Callback>::new_typed()?;
 
     // Callback for handle raised event.
-    let cb: Arc<Callback> = Arc::new(|| -> bool {
+    let cb: Callback = || -> bool {
         let mut input = String::new();
         match stdin().read_line(&mut input) {
             Ok(n) => {
@@ -23,27 +22,21 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
                 false
             }
         }
-    });
+    };
 
     // Add stdin to the watching list of the Poller.
-    poller.add(
-        0,
-        Events::new().read(),
-        Some(Arc::clone(&cb) as EventContext),
-    )?;
+    poller.add(0, Events::new().read(), Some(cb))?;
 
     println!("Press ctrl+c or 'q' to exit ...");
 
     'outer: loop {
         // Pull all events with 1 seconds timeout.
         let events = poller.pull_events(1000)?;
-        for (_fd, _events, _ctx) in events.iter() {
-            // Use EventContext to processing the event.
-            if let Some(x) = _ctx {
-                if let Some(cb) = x.downcast_ref::<Callback>() {
-                    if cb() == false {
-                        break 'outer;
-                    }
+        for (_fd, _events, _ctx, _token) in events.iter() {
+            // Use the callback to processing the event.
+            if let Some(cb) = _ctx {
+                if !cb() {
+                    break 'outer;
                 }
             }
         }
@@ -46,21 +46,21 @@ impl From<timeval> for TimeVal {
     }
 }
 
-impl Into<timeval> for TimeVal {
-    fn into(self) -> timeval {
-        self.inner
+impl From<TimeVal> for timeval {
+    fn from(val: TimeVal) -> Self {
+        val.inner
     }
 }
 
-impl Into<(u32, u32)> for TimeVal {
-    fn into(self) -> (u32, u32) {
-        (self.inner.tv_sec as u32, self.inner.tv_usec as u32)
+impl From<TimeVal> for (u32, u32) {
+    fn from(val: TimeVal) -> Self {
+        (val.inner.tv_sec as u32, val.inner.tv_usec as u32)
     }
 }
 
-impl Into<u64> for TimeVal {
-    fn into(self) -> u64 {
-        self.inner.tv_sec as u64 * 1_000_000u64 + self.inner.tv_usec as u64
+impl From<TimeVal> for u64 {
+    fn from(val: TimeVal) -> Self {
+        val.inner.tv_sec as u64 * 1_000_000u64 + val.inner.tv_usec as u64
     }
 }
 
@@ -107,7 +107,7 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
     'outer: loop {
         // Pull all events with 1 seconds timeout.
         let events = poller.pull_events(1000)?;
-        for (_fd, _events, _ctx) in events.iter() {
+        for (_fd, _events, _ctx, _token) in events.iter() {
             // Exit loop if press any key.
             if _fd == &0 {
                 break 'outer;
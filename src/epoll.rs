@@ -1,10 +1,22 @@
 //! Linux 增强型 I/O 事件通知。
 //!
-use crate::{Events, SysError};
-use libc::{close, epoll_create1, epoll_ctl, epoll_wait};
-use std::any::Any;
-use std::collections::HashMap;
-use std::sync::Arc;
+use crate::signal::SigSet;
+use crate::{timeout_to_ms, to_timespec, Backend, Events, KernelEntry, RawEvent, SysError};
+use libc::{close, epoll_create1, epoll_ctl, epoll_pwait, epoll_wait};
+use std::os::unix::io::{AsRawFd, RawFd};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// 内核是否不支持 `epoll_pwait2`（Linux 5.11 加入）。
+static NO_PWAIT2: AtomicBool = AtomicBool::new(false);
+
+/// 内核中 `sigset_t` 的字节数，与 libc 中的定义不同。
+#[cfg(any(target_arch = "mips", target_arch = "mips64"))]
+const KERNEL_SIGSET_SIZE: usize = 16;
+
+/// 内核中 `sigset_t` 的字节数，与 libc 中的定义不同。
+#[cfg(not(any(target_arch = "mips", target_arch = "mips64")))]
+const KERNEL_SIGSET_SIZE: usize = 8;
 
 impl From<u32> for Events {
     fn from(val: u32) -> Self {
@@ -18,56 +30,75 @@ impl From<u32> for Events {
         if (val & libc::EPOLLERR as u32) == libc::EPOLLERR as u32 {
             events = events.error();
         }
+        if (val & libc::EPOLLET as u32) == libc::EPOLLET as u32 {
+            events = events.edge_triggered();
+        }
+        if (val & libc::EPOLLHUP as u32) == libc::EPOLLHUP as u32 {
+            events = events.hang_up();
+        }
+        if (val & libc::EPOLLONESHOT as u32) == libc::EPOLLONESHOT as u32 {
+            events = events.one_shot();
+        }
+        if (val & libc::EPOLLPRI as u32) == libc::EPOLLPRI as u32 {
+            events = events.priority();
+        }
+        if (val & libc::EPOLLRDHUP as u32) == libc::EPOLLRDHUP as u32 {
+            events = events.read_hang_up();
+        }
+        if (val & libc::EPOLLEXCLUSIVE as u32) == libc::EPOLLEXCLUSIVE as u32 {
+            events = events.exclusive();
+        }
+        if (val & libc::EPOLLWAKEUP as u32) == libc::EPOLLWAKEUP as u32 {
+            events = events.wake_up();
+        }
         events
     }
 }
 
-impl Into<u32> for Events {
-    fn into(self) -> u32 {
+impl From<Events> for u32 {
+    fn from(val: Events) -> Self {
         let mut events = 0u32;
-        if self.has_read() {
+        if val.has_read() {
             events |= libc::EPOLLIN as u32;
         }
-        if self.has_write() {
+        if val.has_write() {
             events |= libc::EPOLLOUT as u32;
         }
-        if self.has_error() {
+        if val.has_error() {
             events |= libc::EPOLLERR as u32;
         }
+        if val.has_edge_triggered() {
+            events |= libc::EPOLLET as u32;
+        }
+        if val.has_hang_up() {
+            events |= libc::EPOLLHUP as u32;
+        }
+        if val.has_one_shot() {
+            events |= libc::EPOLLONESHOT as u32;
+        }
+        if val.has_priority() {
+            events |= libc::EPOLLPRI as u32;
+        }
+        if val.has_read_hang_up() {
+            events |= libc::EPOLLRDHUP as u32;
+        }
+        if val.has_exclusive() {
+            events |= libc::EPOLLEXCLUSIVE as u32;
+        }
+        if val.has_wake_up() {
+            events |= libc::EPOLLWAKEUP as u32;
+        }
         events
     }
 }
 
-/// 定义事件关联上下文。
-pub type EventContext = Arc<dyn Any + Send + Sync>;
-
-/// 定义事件数据。
-///
-/// # Fields
-/// * `0` - 触发的文件描述符。
-/// * `1` - 触发的事件集合。
-/// * `2` - 触发的事件对应上下文。
-pub type EventData<'a> = (i32, Events, Option<&'a EventContext>);
-
-/// 定义文件 I/O 事件通知器。
-///
-/// 每个实例可以管理多个 `fd` 的 I/O 事件。
+/// 基于 `epoll(7)` 的后端。
 #[derive(Debug)]
-pub struct Poller {
+pub struct Epoll {
     epoll_fd: i32,
-    watches: HashMap<i32, (Events, Option<EventContext>)>,
 }
 
-impl Default for Poller {
-    fn default() -> Self {
-        Self {
-            epoll_fd: -1,
-            watches: HashMap::new(),
-        }
-    }
-}
-
-impl Drop for Poller {
+impl Drop for Epoll {
     fn drop(&mut self) {
         if self.epoll_fd > 0 {
             unsafe {
@@ -78,93 +109,182 @@ impl Drop for Poller {
     }
 }
 
-impl Poller {
-    /// 创建一个新的 I/O 事件通知器。
+impl AsRawFd for Epoll {
+    fn as_raw_fd(&self) -> RawFd {
+        self.epoll_fd
+    }
+}
+
+/// 将内核内部的设备编号（高 12 位为主设备号，低 20 位为次设备号）转换为 `st_dev` 的编码。
+fn from_kernel_dev(dev: u64) -> u64 {
+    libc::makedev((dev >> 20) as u32, (dev & 0xfffff) as u32) as u64
+}
+
+/// 解析 `/proc/self/fdinfo/<epoll_fd>` 中的注册项，每项形如：
+///
+/// `tfd:        5 events:       19 data:                5  pos:0 ino:61f sdev:d`
+fn parse_fdinfo(info: &str) -> Vec<KernelEntry> {
+    info.lines()
+        .filter(|x| x.starts_with("tfd:"))
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let mut entry = KernelEntry {
+                fd: -1,
+                events: Events::new(),
+                data: 0,
+                ino: 0,
+                dev: 0,
+            };
+            while let Some(field) = fields.next() {
+                // 字段名与值之间可能有空格，也可能紧挨着。
+                let (name, value) = match field.split_once(':') {
+                    Some((name, "")) => (name, fields.next()?),
+                    Some(x) => x,
+                    None => continue,
+                };
+                match name {
+                    "tfd" => entry.fd = value.parse().ok()?,
+                    "events" => entry.events = Events::from(u32::from_str_radix(value, 16).ok()?),
+                    "data" => entry.data = u64::from_str_radix(value, 16).ok()?,
+                    "ino" => entry.ino = u64::from_str_radix(value, 16).ok()?,
+                    "sdev" => entry.dev = from_kernel_dev(u64::from_str_radix(value, 16).ok()?),
+                    _ => {}
+                }
+            }
+            Some(entry)
+        })
+        .collect()
+}
+
+impl Epoll {
+    /// 创建一个新的 `epoll` 实例。
     pub fn new() -> Result<Self, SysError> {
         let epoll_fd = unsafe { epoll_create1(0) };
         if epoll_fd < 0 {
             Err(SysError::last())
         } else {
-            Ok(Self {
-                epoll_fd,
-                watches: HashMap::new(),
-            })
-        }
-    }
-
-    /// 添加一个文件描述符到监视列表中。
-    ///
-    /// **注意：** 此函数不会把 `fd` 的所有权转移到 `Poller` 内，请确保在 `Poller` 活动期内 `fd` 都是可用的。
-    pub fn add(
-        &mut self,
-        fd: i32,
-        events: Events,
-        ctx: Option<EventContext>,
-    ) -> Result<(), SysError> {
+            Ok(Self { epoll_fd })
+        }
+    }
+
+    fn ctl(&self, op: i32, fd: i32, events: Events, data: u64) -> Result<(), SysError> {
+        let mut ev = libc::epoll_event {
+            events: events.into(),
+            u64: data,
+        };
+        let err = unsafe { epoll_ctl(self.epoll_fd, op, fd, &mut ev) };
+        if err < 0 {
+            Err(SysError::last())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// 等待事件，超时不是整数毫秒时优先使用 `epoll_pwait2` 以获得纳秒精度。
+    fn epoll_wait(
+        &self,
+        buf: *mut libc::epoll_event,
+        len: i32,
+        timeout: Option<Duration>,
+        sigmask: Option<&SigSet>,
+    ) -> i32 {
+        let pmask = sigmask.map_or(std::ptr::null(), |x| x.as_ptr());
+        if let Some(d) = timeout {
+            if d.subsec_nanos() % 1_000_000 != 0 && !NO_PWAIT2.load(Ordering::Relaxed) {
+                let ts = to_timespec(d);
+                let nfds = unsafe {
+                    libc::syscall(
+                        libc::SYS_epoll_pwait2,
+                        self.epoll_fd,
+                        buf,
+                        len,
+                        &ts as *const libc::timespec,
+                        pmask,
+                        KERNEL_SIGSET_SIZE,
+                    )
+                };
+                if nfds >= 0 {
+                    return nfds as i32;
+                }
+                // 旧内核返回 ENOSYS，部分容器的 seccomp 策略返回 EPERM。
+                match SysError::last().into() {
+                    libc::ENOSYS | libc::EPERM => NO_PWAIT2.store(true, Ordering::Relaxed),
+                    _ => return -1,
+                }
+            }
+        }
+        let timeout_ms = timeout_to_ms(timeout);
         unsafe {
-            let mut ev = libc::epoll_event {
-                events: events.into(),
-                u64: fd as u64,
-            };
-            let err = epoll_ctl(self.epoll_fd, libc::EPOLL_CTL_ADD, fd, &mut ev);
-            if err < 0 {
-                return Err(SysError::last());
+            match sigmask {
+                Some(_) => epoll_pwait(self.epoll_fd, buf, len, timeout_ms, pmask),
+                None => epoll_wait(self.epoll_fd, buf, len, timeout_ms),
             }
-            self.watches.insert(fd, (events, ctx));
-            Ok(())
         }
     }
 
-    /// 将一个文件描述符从监视列表中移除。
-    pub fn remove(&mut self, fd: i32) -> Result<(), SysError> {
-        if !self.watches.contains_key(&fd) {
-            return Err(SysError::from(libc::ENOENT));
+    fn wait_events(
+        &self,
+        events: &mut [RawEvent],
+        timeout: Option<Duration>,
+        sigmask: Option<&SigSet>,
+    ) -> Result<usize, SysError> {
+        let out = events.as_mut_ptr();
+        let buf = out as *mut libc::epoll_event;
+        let nfds = self.epoll_wait(buf, events.len() as i32, timeout, sigmask);
+        if nfds < 0 {
+            return Err(SysError::last());
         }
+        // 倒序原地转换，写入第 i 项时不会覆盖前面尚未读取的 `epoll_event`。
+        for i in (0..nfds as usize).rev() {
+            unsafe {
+                let ev = std::ptr::read_unaligned(buf.add(i));
+                std::ptr::write(out.add(i), (ev.u64, Events::from(ev.events)));
+            }
+        }
+        Ok(nfds as usize)
+    }
+}
+
+// `RawEvent` 不小于 `epoll_event`，`wait` 会直接复用调用者的缓冲区接收内核事件。
+const _: () = assert!(std::mem::size_of::<RawEvent>() >= std::mem::size_of::<libc::epoll_event>());
+
+impl Backend for Epoll {
+    fn register(&self, fd: i32, events: Events, data: u64) -> Result<(), SysError> {
+        self.ctl(libc::EPOLL_CTL_ADD, fd, events, data)
+    }
+
+    fn modify(&self, fd: i32, events: Events, data: u64) -> Result<(), SysError> {
+        self.ctl(libc::EPOLL_CTL_MOD, fd, events, data)
+    }
+
+    fn deregister(&self, fd: i32) -> Result<(), SysError> {
         let err =
             unsafe { epoll_ctl(self.epoll_fd, libc::EPOLL_CTL_DEL, fd, std::ptr::null_mut()) };
         if err < 0 {
             Err(SysError::last())
         } else {
-            self.watches.remove(&fd).unwrap();
             Ok(())
         }
     }
 
-    /// 拉取所有被监测到的 I/O 事件。
-    ///
-    /// # Examples
-    ///
-    /// ```
-    /// use poller::{Events, Poller};
-    /// let mut poller = Poller::new().unwrap();
-    /// poller.add(1, Events::new().write(), None).unwrap();
-    /// for (fd, events, ctx) in poller.pull_events(1000).unwrap().iter() {
-    ///     println!("Fd={}, Events={}, Context={:?}", fd, events, ctx);
-    /// }
-    /// ```
-    pub fn pull_events(&self, timeout_ms: i32) -> Result<Vec<EventData>, SysError> {
-        unsafe {
-            let mut ev: Vec<libc::epoll_event> = Vec::with_capacity(self.watches.len());
-            let nfds = epoll_wait(
-                self.epoll_fd,
-                ev.as_mut_ptr(),
-                self.watches.len() as i32,
-                timeout_ms,
-            );
-            if nfds < 0 {
-                return Err(SysError::last());
-            }
-            ev.set_len(nfds as usize);
-            Ok(ev
-                .into_iter()
-                .map(|x| {
-                    if let Some(v) = self.watches.get(&(x.u64 as i32)) {
-                        (x.u64 as i32, Events::from(x.events), v.1.as_ref())
-                    } else {
-                        (x.u64 as i32, Events::from(x.events), None)
-                    }
-                })
-                .collect())
+    fn wait(&self, events: &mut [RawEvent], timeout: Option<Duration>) -> Result<usize, SysError> {
+        self.wait_events(events, timeout, None)
+    }
+
+    fn wait_with_sigmask(
+        &self,
+        events: &mut [RawEvent],
+        timeout: Option<Duration>,
+        sigmask: &SigSet,
+    ) -> Result<usize, SysError> {
+        self.wait_events(events, timeout, Some(sigmask))
+    }
+
+    fn entries(&self) -> Result<Vec<KernelEntry>, SysError> {
+        let path = format!("/proc/self/fdinfo/{}", self.epoll_fd);
+        match std::fs::read_to_string(path) {
+            Ok(info) => Ok(parse_fdinfo(&info)),
+            Err(e) => Err(SysError::from(e.raw_os_error().unwrap_or(libc::EIO))),
         }
     }
 }
@@ -172,23 +292,61 @@ impl Poller {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::Poller;
 
     #[test]
     fn test_poller() {
         unsafe {
             let cstr = std::ffi::CString::new("/proc/uptime").unwrap();
             let fd = libc::open(cstr.as_ptr(), libc::O_RDONLY);
-            let mut poller = Poller::new().unwrap();
-            assert_eq!(poller.add(fd, Events::new().read(), None).is_ok(), true);
+            let mut poller = Poller::with_backend(Box::new(Epoll::new().unwrap()));
+            assert!(poller.add(fd, Events::new().read(), None).is_ok());
             for _ in 0..1000 {
                 assert_eq!(poller.pull_events(1000).unwrap().len(), 1);
             }
-            assert_eq!(poller.remove(fd).is_ok(), true);
+            assert!(poller.remove(fd).is_ok());
             for _ in 0..1000 {
-                assert_eq!(poller.add(fd, Events::new().read(), None).is_ok(), true);
-                assert_eq!(poller.remove(fd).is_ok(), true);
+                assert!(poller.add(fd, Events::new().read(), None).is_ok());
+                assert!(poller.remove(fd).is_ok());
             }
             libc::close(fd);
         }
     }
+
+    #[test]
+    fn test_parse_fdinfo() {
+        let info = "pos:\t0\nflags:\t02000002\nmnt_id:\t17\n\
+            tfd:        5 events: 8000001c data:         10000000a  pos:0 ino:6fde sdev:f\n\
+            tfd:        4 events: 40000000 data:                4  pos:0 ino:61f sdev:800001\n";
+        let entries = parse_fdinfo(info);
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].fd, 5);
+        assert_eq!(entries[0].data, 0x1_0000_000a);
+        assert_eq!(entries[0].ino, 0x6fde);
+        assert_eq!(entries[0].dev, libc::makedev(0, 0xf) as u64);
+        assert_eq!(entries[1].dev, libc::makedev(8, 1) as u64);
+        assert!(entries[0].events.has_write() && entries[0].events.has_edge_triggered());
+        assert_eq!(entries[1].events, Events::new().one_shot());
+    }
+
+    #[test]
+    fn test_events_round_trip() {
+        let events = Events::new()
+            .read()
+            .write()
+            .error()
+            .edge_triggered()
+            .hang_up()
+            .one_shot()
+            .priority()
+            .read_hang_up()
+            .exclusive()
+            .wake_up();
+        let bits: u32 = events.into();
+        assert_eq!(Events::from(bits), events);
+        assert_eq!(bits & libc::EPOLLET as u32, libc::EPOLLET as u32);
+        assert_eq!(bits & libc::EPOLLPRI as u32, libc::EPOLLPRI as u32);
+        assert!(Events::from(libc::EPOLLHUP as u32).has_hang_up());
+        assert!(Events::from(libc::EPOLLRDHUP as u32).has_read_hang_up());
+    }
 }
@@ -0,0 +1,292 @@
+//! 类似 `tail -F` 的文件跟随读取。
+//!
+use crate::inotify::{FileWatcher, WatchDescriptor, WatchMask};
+use crate::SysError;
+use std::fs::File;
+use std::io::{Read, Seek, SeekFrom};
+use std::os::unix::fs::MetadataExt;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// 每次调用最多读取的字节数，避免一次把整个文件读入内存。
+const MAX_READ: usize = 64 * 1024;
+
+/// 将 `std::io::Error` 转换为 `SysError`。
+fn io_error(e: std::io::Error) -> SysError {
+    SysError::from(e.raw_os_error().unwrap_or(libc::EIO))
+}
+
+/// 定义跟随状态。
+#[derive(Debug, Default)]
+struct State {
+    file: Option<File>,
+    /// 当前打开的文件的设备及 inode 编号。
+    id: (u64, u64),
+    /// 下次读取的位置。
+    pos: u64,
+    /// 当前打开的文件的监视。
+    wd: Option<WatchDescriptor>,
+    /// 尚未以换行结尾的数据。
+    partial: Vec<u8>,
+}
+
+/// 定义文件跟随器。
+///
+/// 持续读取日志等文件新追加的数据，文件被截断时从头读取，被轮转（移走或删除后重新创建，
+/// 即路径指向的 inode 改变）时读完原文件剩余的数据后打开新文件。
+///
+/// 通过 inotify 监视文件及其所在的目录，可以与其他 `fd` 一起添加到 `Poller` 中，
+/// 收到可读事件后调用 `read_lines` 或 `read` 读取，两者不应混用。
+///
+/// 每次调用最多读取 64 KiB，未读完的数据不会再产生可读事件，收到事件后应重复调用直到返回空。
+///
+/// # Examples
+///
+/// ```
+/// use poller::follow::FileFollower;
+/// use poller::{Events, Poller};
+/// let mut poller = Poller::new().unwrap();
+/// let follower = FileFollower::new(std::env::temp_dir().join("app.log")).unwrap();
+/// let _reg = poller.add_fd(&follower, Events::new().read(), None).unwrap();
+/// for (_fd, _events, _ctx, _token) in poller.pull_events(0).unwrap() {
+///     for line in follower.read_lines().unwrap() {
+///         println!("{}", line);
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct FileFollower {
+    path: PathBuf,
+    watcher: FileWatcher,
+    state: Mutex<State>,
+}
+
+impl AsRawFd for FileFollower {
+    fn as_raw_fd(&self) -> RawFd {
+        self.watcher.as_raw_fd()
+    }
+}
+
+impl AsFd for FileFollower {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.watcher.as_fd()
+    }
+}
+
+impl FileFollower {
+    /// 创建一个从 `path` 当前末尾开始读取的跟随器。
+    ///
+    /// 文件不存在时等待其被创建，但所在的目录必须存在。
+    pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, SysError> {
+        let path = path.as_ref().to_path_buf();
+        let dir = path
+            .parent()
+            .filter(|x| !x.as_os_str().is_empty())
+            .unwrap_or_else(|| Path::new("."));
+        let watcher = FileWatcher::new()?;
+        watcher.add_watch(dir, WatchMask::new().create().moved_to().only_dir())?;
+        let this = Self {
+            path,
+            watcher,
+            state: Mutex::new(State::default()),
+        };
+        this.reopen(&mut this.state.lock().unwrap(), true)?;
+        Ok(this)
+    }
+
+    /// 设置从文件开头而不是末尾开始读取。
+    ///
+    /// 文件发生变化前不会产生可读事件，需要先调用一次 `read_lines` 或 `read` 读取已有的数据。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use poller::follow::FileFollower;
+    /// let follower = FileFollower::new(std::env::temp_dir().join("app.log")).unwrap().from_start();
+    /// ```
+    pub fn from_start(self) -> Self {
+        self.state.lock().unwrap().pos = 0;
+        self
+    }
+
+    /// 返回跟随的文件路径。
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 读取新追加的数据，最多 64 KiB，没有新数据时返回空。
+    pub fn read(&self) -> Result<Vec<u8>, SysError> {
+        let mut state = self.state.lock().unwrap();
+        let mut buf = std::mem::take(&mut state.partial);
+        for (_, data) in self.read_new(&mut state)? {
+            buf.extend(data);
+        }
+        Ok(buf)
+    }
+
+    /// 读取新追加的完整行，不包括行尾的换行符，没有新的完整行时返回空列表。
+    ///
+    /// 每次最多读取 64 KiB，尚未得到完整的行时继续读取，因此返回空列表说明数据已读完。
+    /// 未以换行结尾的数据留到下次读取；文件被截断或轮转时，这部分数据作为一行返回。
+    pub fn read_lines(&self) -> Result<Vec<String>, SysError> {
+        let mut state = self.state.lock().unwrap();
+        let mut lines = Vec::new();
+        loop {
+            let segments = self.read_new(&mut state)?;
+            let done = segments.iter().all(|x| x.1.is_empty());
+            for (reset, data) in segments {
+                if reset && !state.partial.is_empty() {
+                    let line = std::mem::take(&mut state.partial);
+                    lines.push(String::from_utf8_lossy(&line).into_owned());
+                }
+                state.partial.extend(data);
+                while let Some(n) = state.partial.iter().position(|x| *x == b'\n') {
+                    let line: Vec<u8> = state.partial.drain(..=n).collect();
+                    lines.push(String::from_utf8_lossy(&line[..n]).into_owned());
+                }
+            }
+            if done || !lines.is_empty() {
+                return Ok(lines);
+            }
+        }
+    }
+
+    /// 打开 `path` 当前指向的文件，文件不存在时返回 `false`。
+    fn reopen(&self, state: &mut State, at_end: bool) -> Result<bool, SysError> {
+        let file = match File::open(&self.path) {
+            Ok(x) => x,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(io_error(e)),
+        };
+        let meta = file.metadata().map_err(io_error)?;
+        if let Some(wd) = state.wd.take() {
+            let _ = self.watcher.remove_watch(wd);
+        }
+        let mask = WatchMask::new().modify().attrib().move_self().delete_self();
+        state.wd = Some(self.watcher.add_watch(&self.path, mask)?);
+        state.id = (meta.dev(), meta.ino());
+        state.pos = if at_end { meta.len() } else { 0 };
+        state.file = Some(file);
+        Ok(true)
+    }
+
+    /// 从当前位置最多读取 `limit` 字节，返回文件是否被截断及读取到的数据。
+    fn read_file(&self, state: &mut State, limit: usize) -> Result<(bool, Vec<u8>), SysError> {
+        let mut data = Vec::new();
+        let file = match state.file.as_mut() {
+            Some(x) => x,
+            None => return Ok((false, data)),
+        };
+        let len = file.metadata().map_err(io_error)?.len();
+        let truncated = len < state.pos;
+        if truncated {
+            state.pos = 0;
+        }
+        file.seek(SeekFrom::Start(state.pos)).map_err(io_error)?;
+        let n = file.take(limit as u64).read_to_end(&mut data);
+        state.pos += n.map_err(io_error)? as u64;
+        Ok((truncated, data))
+    }
+
+    /// 读取所有新数据，每段数据前的标志表示此前文件是否被截断或轮转。
+    fn read_new(&self, state: &mut State) -> Result<Vec<(bool, Vec<u8>)>, SysError> {
+        // 事件只用于唤醒，变化通过文件状态判断。
+        self.watcher.read_events()?;
+        let mut segments = vec![self.read_file(state, MAX_READ)?];
+        let left = MAX_READ - segments[0].1.len();
+        // 原文件读完后才切换到新文件。
+        if left == 0 {
+            return Ok(segments);
+        }
+        let current = std::fs::metadata(&self.path)
+            .ok()
+            .map(|x| (x.dev(), x.ino()));
+        let rotated = match current {
+            Some(id) => state.file.is_none() || id != state.id,
+            None => false,
+        };
+        if rotated && self.reopen(state, false)? {
+            let (_, data) = self.read_file(state, left)?;
+            segments.push((true, data));
+        }
+        segments.retain(|x| x.0 || !x.1.is_empty());
+        Ok(segments)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Events, Poller};
+    use std::io::Write;
+
+    #[test]
+    fn test_file_follower() {
+        let dir = std::env::temp_dir().join(format!("poller-follow-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("log");
+        std::fs::write(&path, b"old\n").unwrap();
+        let mut poller = Poller::new().unwrap();
+        let follower = FileFollower::new(&path).unwrap();
+        let _reg = poller
+            .add_fd(&follower, Events::new().read(), None)
+            .unwrap();
+        let wait = |n: usize| {
+            let mut lines = Vec::new();
+            while lines.len() < n && !poller.pull_events(1000).unwrap().is_empty() {
+                lines.extend(follower.read_lines().unwrap());
+            }
+            lines
+        };
+        assert!(follower.read_lines().unwrap().is_empty());
+        let append = |data: &[u8]| {
+            let mut file = std::fs::OpenOptions::new()
+                .append(true)
+                .open(&path)
+                .unwrap();
+            file.write_all(data).unwrap();
+        };
+        append(b"x\ny");
+        assert_eq!(wait(1), vec!["x"]);
+        append(b"z\n");
+        assert_eq!(wait(1), vec!["yz"]);
+        // 截断后从头读取。
+        std::fs::write(&path, b"t\n").unwrap();
+        assert_eq!(wait(1), vec!["t"]);
+        // 轮转时先读完原文件。
+        append(b"r\n");
+        std::fs::rename(&path, dir.join("log.1")).unwrap();
+        std::fs::write(&path, b"n\n").unwrap();
+        assert_eq!(wait(2), vec!["r", "n"]);
+        append(b"chunk");
+        let mut data = Vec::new();
+        while data.len() < 5 && !poller.pull_events(1000).unwrap().is_empty() {
+            data.extend(follower.read().unwrap());
+        }
+        assert_eq!(data, b"chunk");
+        // 超出上限的数据不会再产生事件，需要重复读取直到返回空。
+        let len = MAX_READ * 2 + 1;
+        append(&vec![b'a'; len]);
+        let mut total = 0;
+        while total < len && !poller.pull_events(1000).unwrap().is_empty() {
+            loop {
+                let n = follower.read().unwrap().len();
+                assert!(n <= MAX_READ);
+                if n == 0 {
+                    break;
+                }
+                total += n;
+            }
+        }
+        assert_eq!(total, len);
+        assert!(poller.pull_events(100).unwrap().is_empty());
+        // 没有得到完整的行时继续读取，超出上限的行一次返回。
+        append(&vec![b'b'; len]);
+        append(b"\n");
+        let lines = wait(1);
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0].len(), len);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
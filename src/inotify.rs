@@ -0,0 +1,744 @@
+//! 基于 `inotify(7)` 的文件系统变化通知。
+//!
+//! `FileWatcher` 直接对应 inotify 的接口，`RecursiveWatcher` 在其上实现了目录树的递归监视。
+use crate::SysError;
+use std::collections::HashMap;
+use std::ffi::{CString, OsStr, OsString};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// 定义监视掩码，即 `IN_*` 标志的集合。
+///
+/// 既用于添加监视时指定关注的事件，也用于表示收到的事件。
+///
+/// # Examples
+///
+/// ```
+/// use poller::inotify::WatchMask;
+/// let mask = WatchMask::new().create().delete().close_write();
+/// assert!(mask.has_create());
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct WatchMask(u32);
+
+impl std::fmt::Display for WatchMask {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "0x{:08X}", self.0)
+    }
+}
+
+impl From<u32> for WatchMask {
+    fn from(val: u32) -> Self {
+        Self(val)
+    }
+}
+
+impl From<WatchMask> for u32 {
+    fn from(val: WatchMask) -> Self {
+        val.0
+    }
+}
+
+impl WatchMask {
+    /// 创建一个空的监视掩码。
+    pub fn new() -> Self {
+        Self(0)
+    }
+
+    /// 附加文件被访问（`IN_ACCESS`）到掩码中。
+    pub fn access(mut self) -> Self {
+        self.0 |= libc::IN_ACCESS;
+        self
+    }
+
+    /// 附加文件被修改（`IN_MODIFY`）到掩码中。
+    pub fn modify(mut self) -> Self {
+        self.0 |= libc::IN_MODIFY;
+        self
+    }
+
+    /// 附加元数据被修改（`IN_ATTRIB`）到掩码中。
+    pub fn attrib(mut self) -> Self {
+        self.0 |= libc::IN_ATTRIB;
+        self
+    }
+
+    /// 附加以可写方式打开的文件被关闭（`IN_CLOSE_WRITE`）到掩码中。
+    pub fn close_write(mut self) -> Self {
+        self.0 |= libc::IN_CLOSE_WRITE;
+        self
+    }
+
+    /// 附加以只读方式打开的文件被关闭（`IN_CLOSE_NOWRITE`）到掩码中。
+    pub fn close_nowrite(mut self) -> Self {
+        self.0 |= libc::IN_CLOSE_NOWRITE;
+        self
+    }
+
+    /// 附加文件被打开（`IN_OPEN`）到掩码中。
+    pub fn open(mut self) -> Self {
+        self.0 |= libc::IN_OPEN;
+        self
+    }
+
+    /// 附加文件被移出目录（`IN_MOVED_FROM`）到掩码中。
+    pub fn moved_from(mut self) -> Self {
+        self.0 |= libc::IN_MOVED_FROM;
+        self
+    }
+
+    /// 附加文件被移入目录（`IN_MOVED_TO`）到掩码中。
+    pub fn moved_to(mut self) -> Self {
+        self.0 |= libc::IN_MOVED_TO;
+        self
+    }
+
+    /// 附加目录中创建了文件（`IN_CREATE`）到掩码中。
+    pub fn create(mut self) -> Self {
+        self.0 |= libc::IN_CREATE;
+        self
+    }
+
+    /// 附加目录中删除了文件（`IN_DELETE`）到掩码中。
+    pub fn delete(mut self) -> Self {
+        self.0 |= libc::IN_DELETE;
+        self
+    }
+
+    /// 附加被监视的文件自身被删除（`IN_DELETE_SELF`）到掩码中。
+    pub fn delete_self(mut self) -> Self {
+        self.0 |= libc::IN_DELETE_SELF;
+        self
+    }
+
+    /// 附加被监视的文件自身被移动（`IN_MOVE_SELF`）到掩码中。
+    pub fn move_self(mut self) -> Self {
+        self.0 |= libc::IN_MOVE_SELF;
+        self
+    }
+
+    /// 附加所有文件事件（`IN_ALL_EVENTS`）到掩码中。
+    pub fn all(mut self) -> Self {
+        self.0 |= libc::IN_ALL_EVENTS;
+        self
+    }
+
+    /// 附加不跟随符号链接（`IN_DONT_FOLLOW`）标志到掩码中。
+    pub fn dont_follow(mut self) -> Self {
+        self.0 |= libc::IN_DONT_FOLLOW;
+        self
+    }
+
+    /// 附加忽略已删除的子项（`IN_EXCL_UNLINK`）标志到掩码中。
+    pub fn excl_unlink(mut self) -> Self {
+        self.0 |= libc::IN_EXCL_UNLINK;
+        self
+    }
+
+    /// 附加合并到已有监视的掩码（`IN_MASK_ADD`）标志到掩码中。
+    pub fn mask_add(mut self) -> Self {
+        self.0 |= libc::IN_MASK_ADD;
+        self
+    }
+
+    /// 附加单次触发（`IN_ONESHOT`）标志到掩码中。
+    pub fn one_shot(mut self) -> Self {
+        self.0 |= libc::IN_ONESHOT;
+        self
+    }
+
+    /// 附加仅监视目录（`IN_ONLYDIR`）标志到掩码中。
+    pub fn only_dir(mut self) -> Self {
+        self.0 |= libc::IN_ONLYDIR;
+        self
+    }
+
+    /// 检查掩码是否为空。
+    pub fn is_none(self) -> bool {
+        self.0 == 0
+    }
+
+    /// 检查掩码是否有文件被访问。
+    pub fn has_access(self) -> bool {
+        (self.0 & libc::IN_ACCESS) != 0
+    }
+
+    /// 检查掩码是否有文件被修改。
+    pub fn has_modify(self) -> bool {
+        (self.0 & libc::IN_MODIFY) != 0
+    }
+
+    /// 检查掩码是否有元数据被修改。
+    pub fn has_attrib(self) -> bool {
+        (self.0 & libc::IN_ATTRIB) != 0
+    }
+
+    /// 检查掩码是否有以可写方式打开的文件被关闭。
+    pub fn has_close_write(self) -> bool {
+        (self.0 & libc::IN_CLOSE_WRITE) != 0
+    }
+
+    /// 检查掩码是否有以只读方式打开的文件被关闭。
+    pub fn has_close_nowrite(self) -> bool {
+        (self.0 & libc::IN_CLOSE_NOWRITE) != 0
+    }
+
+    /// 检查掩码是否有文件被打开。
+    pub fn has_open(self) -> bool {
+        (self.0 & libc::IN_OPEN) != 0
+    }
+
+    /// 检查掩码是否有文件被移出目录。
+    pub fn has_moved_from(self) -> bool {
+        (self.0 & libc::IN_MOVED_FROM) != 0
+    }
+
+    /// 检查掩码是否有文件被移入目录。
+    pub fn has_moved_to(self) -> bool {
+        (self.0 & libc::IN_MOVED_TO) != 0
+    }
+
+    /// 检查掩码是否有目录中创建了文件。
+    pub fn has_create(self) -> bool {
+        (self.0 & libc::IN_CREATE) != 0
+    }
+
+    /// 检查掩码是否有目录中删除了文件。
+    pub fn has_delete(self) -> bool {
+        (self.0 & libc::IN_DELETE) != 0
+    }
+
+    /// 检查掩码是否有被监视的文件自身被删除。
+    pub fn has_delete_self(self) -> bool {
+        (self.0 & libc::IN_DELETE_SELF) != 0
+    }
+
+    /// 检查掩码是否有被监视的文件自身被移动。
+    pub fn has_move_self(self) -> bool {
+        (self.0 & libc::IN_MOVE_SELF) != 0
+    }
+
+    /// 检查事件的对象是否为目录（`IN_ISDIR`）。
+    pub fn is_dir(self) -> bool {
+        (self.0 & libc::IN_ISDIR) != 0
+    }
+
+    /// 检查监视是否已被移除（`IN_IGNORED`），包括主动移除及文件被删除等。
+    pub fn has_ignored(self) -> bool {
+        (self.0 & libc::IN_IGNORED) != 0
+    }
+
+    /// 检查事件队列是否已溢出（`IN_Q_OVERFLOW`），此时有事件被丢弃。
+    pub fn has_q_overflow(self) -> bool {
+        (self.0 & libc::IN_Q_OVERFLOW) != 0
+    }
+
+    /// 检查被监视文件所在的文件系统是否已被卸载（`IN_UNMOUNT`）。
+    pub fn has_unmount(self) -> bool {
+        (self.0 & libc::IN_UNMOUNT) != 0
+    }
+}
+
+/// 定义监视描述符，由 `FileWatcher::add_watch` 返回。
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct WatchDescriptor(i32);
+
+impl From<WatchDescriptor> for i32 {
+    fn from(val: WatchDescriptor) -> Self {
+        val.0
+    }
+}
+
+/// 定义文件系统事件。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FileEvent {
+    /// 触发事件的监视，队列溢出时为 -1。
+    pub wd: WatchDescriptor,
+    /// 事件掩码。
+    pub mask: WatchMask,
+    /// 关联同一次重命名的 `IN_MOVED_FROM` 与 `IN_MOVED_TO`，其他事件为 0。
+    pub cookie: u32,
+    /// 被监视目录中触发事件的文件名，事件针对被监视对象自身时为 `None`。
+    pub name: Option<OsString>,
+}
+
+/// 每次读取的缓冲区大小，至少能容纳一个带有最长文件名的事件。
+const BUFFER_SIZE: usize = 16 * (std::mem::size_of::<libc::inotify_event>() + 256);
+
+/// 解析 `read(2)` 读取到的 `inotify_event` 序列，每项之后跟随 `len` 字节以 0 填充的文件名。
+fn parse_events(buf: &[u8]) -> Vec<FileEvent> {
+    const HEADER: usize = std::mem::size_of::<libc::inotify_event>();
+    let mut events = Vec::new();
+    let mut offset = 0;
+    while offset + HEADER <= buf.len() {
+        let ev = unsafe {
+            std::ptr::read_unaligned(buf[offset..].as_ptr() as *const libc::inotify_event)
+        };
+        let start = offset + HEADER;
+        let end = (start + ev.len as usize).min(buf.len());
+        let name = &buf[start..end];
+        let name = &name[..name.iter().position(|x| *x == 0).unwrap_or(name.len())];
+        events.push(FileEvent {
+            wd: WatchDescriptor(ev.wd),
+            mask: WatchMask(ev.mask),
+            cookie: ev.cookie,
+            name: Some(name)
+                .filter(|x| !x.is_empty())
+                .map(|x| OsStr::from_bytes(x).to_os_string()),
+        });
+        offset = end;
+    }
+    events
+}
+
+/// 定义基于 `inotify(7)` 的文件系统监视器。
+///
+/// 被监视的文件发生变化时文件描述符变为可读，可以与其他 `fd` 一起添加到 `Poller` 中，
+/// 收到可读事件后调用 `read_events` 读取。
+///
+/// # Examples
+///
+/// ```
+/// use poller::inotify::{FileWatcher, WatchMask};
+/// use poller::{Events, Poller};
+/// let mut poller = Poller::new().unwrap();
+/// let watcher = FileWatcher::new().unwrap();
+/// let dir = std::env::temp_dir();
+/// let wd = watcher.add_watch(&dir, WatchMask::new().create().delete()).unwrap();
+/// let _reg = poller.add_fd(&watcher, Events::new().read(), None).unwrap();
+/// for (_fd, _events, _ctx, _token) in poller.pull_events(0).unwrap() {
+///     for event in watcher.read_events().unwrap() {
+///         println!("{:?}: {} {:?}", event.wd, event.mask, event.name);
+///     }
+/// }
+/// watcher.remove_watch(wd).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct FileWatcher {
+    fd: OwnedFd,
+}
+
+impl AsRawFd for FileWatcher {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl AsFd for FileWatcher {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl FileWatcher {
+    /// 创建一个新的文件系统监视器。
+    pub fn new() -> Result<Self, SysError> {
+        let fd = unsafe { libc::inotify_init1(libc::IN_CLOEXEC | libc::IN_NONBLOCK) };
+        if fd < 0 {
+            Err(SysError::last())
+        } else {
+            Ok(Self {
+                fd: unsafe { OwnedFd::from_raw_fd(fd) },
+            })
+        }
+    }
+
+    /// 添加或修改对 `path` 的监视，同一文件（inode）总是返回相同的监视描述符。
+    ///
+    /// 监视目录时目录中的文件发生变化也会触发事件，但不包括子目录中的文件。
+    pub fn add_watch<P: AsRef<Path>>(
+        &self,
+        path: P,
+        mask: WatchMask,
+    ) -> Result<WatchDescriptor, SysError> {
+        let path = CString::new(path.as_ref().as_os_str().as_bytes())
+            .map_err(|_| SysError::from(libc::EINVAL))?;
+        let wd = unsafe { libc::inotify_add_watch(self.fd.as_raw_fd(), path.as_ptr(), mask.0) };
+        if wd < 0 {
+            Err(SysError::last())
+        } else {
+            Ok(WatchDescriptor(wd))
+        }
+    }
+
+    /// 移除一个监视，之后会收到该监视的 `IN_IGNORED` 事件。
+    pub fn remove_watch(&self, wd: WatchDescriptor) -> Result<(), SysError> {
+        if unsafe { libc::inotify_rm_watch(self.fd.as_raw_fd(), wd.0) } < 0 {
+            Err(SysError::last())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// 读取所有待处理的事件，没有事件时返回空列表。
+    pub fn read_events(&self) -> Result<Vec<FileEvent>, SysError> {
+        let mut events = Vec::new();
+        let mut buf = vec![0u8; BUFFER_SIZE];
+        loop {
+            let n =
+                unsafe { libc::read(self.fd.as_raw_fd(), buf.as_mut_ptr() as *mut _, buf.len()) };
+            if n < 0 {
+                match SysError::last() {
+                    e if i32::from(e) == libc::EAGAIN => return Ok(events),
+                    e => return Err(e),
+                }
+            }
+            events.extend(parse_events(&buf[..n as usize]));
+        }
+    }
+}
+
+/// 定义目录树中的变化。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DirEvent {
+    /// 创建了文件或目录，新目录中已有的内容也会逐一上报。
+    Created(PathBuf),
+    /// 文件内容被修改。
+    Modified(PathBuf),
+    /// 删除了文件或目录。
+    Removed(PathBuf),
+    /// 在目录树内重命名或移动。
+    Renamed { from: PathBuf, to: PathBuf },
+    /// 从目录树外移入，移入的目录中已有的内容会作为 `Created` 上报。
+    MovedIn(PathBuf),
+    /// 移出到目录树外。
+    MovedOut(PathBuf),
+    /// 事件队列溢出后重新扫描了目录树，期间的变化可能已丢失。
+    Rescanned,
+}
+
+/// 定义递归的目录监视器。
+///
+/// 监视目录树中所有的目录，新建或移入的子目录会自动添加监视，
+/// 通过 `cookie` 将 `IN_MOVED_FROM` 与 `IN_MOVED_TO` 合并为一个重命名事件，
+/// 事件队列溢出（`IN_Q_OVERFLOW`）时重新扫描目录树。
+///
+/// 与 `FileWatcher` 一样可以添加到 `Poller` 中，收到可读事件后调用 `read_events` 读取。
+///
+/// # Examples
+///
+/// ```
+/// use poller::inotify::RecursiveWatcher;
+/// use poller::{Events, Poller};
+/// let root = std::env::temp_dir().join(format!("poller-doc-{}", std::process::id()));
+/// std::fs::create_dir_all(root.join("sub")).unwrap();
+/// let mut poller = Poller::new().unwrap();
+/// let watcher = RecursiveWatcher::new(&root).unwrap();
+/// let _reg = poller.add_fd(&watcher, Events::new().read(), None).unwrap();
+/// for (_fd, _events, _ctx, _token) in poller.pull_events(0).unwrap() {
+///     for event in watcher.read_events().unwrap() {
+///         println!("{:?}", event);
+///     }
+/// }
+/// std::fs::remove_dir_all(&root).unwrap();
+/// ```
+#[derive(Debug)]
+pub struct RecursiveWatcher {
+    watcher: FileWatcher,
+    root: PathBuf,
+    /// 被监视的目录。
+    dirs: Mutex<HashMap<WatchDescriptor, PathBuf>>,
+}
+
+impl AsRawFd for RecursiveWatcher {
+    fn as_raw_fd(&self) -> RawFd {
+        self.watcher.as_raw_fd()
+    }
+}
+
+impl AsFd for RecursiveWatcher {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.watcher.as_fd()
+    }
+}
+
+impl RecursiveWatcher {
+    /// 创建一个监视 `root` 目录树的监视器。
+    pub fn new<P: AsRef<Path>>(root: P) -> Result<Self, SysError> {
+        let this = Self {
+            watcher: FileWatcher::new()?,
+            root: root.as_ref().to_path_buf(),
+            dirs: Mutex::new(HashMap::new()),
+        };
+        this.watch_tree(&mut this.dirs.lock().unwrap(), &this.root, None)?;
+        Ok(this)
+    }
+
+    /// 返回被监视的根目录。
+    pub fn root(&self) -> &Path {
+        &self.root
+    }
+
+    /// 返回被监视的目录数量。
+    pub fn len(&self) -> usize {
+        self.dirs.lock().unwrap().len()
+    }
+
+    /// 检查是否没有监视任何目录，根目录被删除后为空。
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn mask() -> WatchMask {
+        WatchMask::new()
+            .create()
+            .delete()
+            .modify()
+            .moved_from()
+            .moved_to()
+            .only_dir()
+            .dont_follow()
+            .excl_unlink()
+    }
+
+    /// 监视 `dir` 及其中所有的子目录，`created` 不为 `None` 时将其中已有的内容作为新建上报。
+    fn watch_tree(
+        &self,
+        dirs: &mut HashMap<WatchDescriptor, PathBuf>,
+        dir: &Path,
+        mut created: Option<&mut Vec<DirEvent>>,
+    ) -> Result<(), SysError> {
+        let wd = self.watcher.add_watch(dir, Self::mask())?;
+        dirs.insert(wd, dir.to_path_buf());
+        // 目录可能已被删除，此时忽略其中的内容。
+        let entries = match std::fs::read_dir(dir) {
+            Ok(x) => x,
+            Err(_) => return Ok(()),
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if let Some(events) = created.as_mut() {
+                events.push(DirEvent::Created(path.clone()));
+            }
+            if entry.file_type().is_ok_and(|x| x.is_dir()) {
+                let _ = self.watch_tree(dirs, &path, created.as_deref_mut());
+            }
+        }
+        Ok(())
+    }
+
+    /// 移除 `dir` 及其中所有子目录的监视。
+    fn unwatch_tree(&self, dirs: &mut HashMap<WatchDescriptor, PathBuf>, dir: &Path) {
+        dirs.retain(|wd, x| {
+            let inside = x.starts_with(dir);
+            if inside {
+                let _ = self.watcher.remove_watch(*wd);
+            }
+            !inside
+        });
+    }
+
+    /// 重新扫描目录树，为遗漏的子目录添加监视并移除已不存在的目录的监视。
+    pub fn rescan(&self) -> Result<(), SysError> {
+        self.rescan_locked(&mut self.dirs.lock().unwrap())
+    }
+
+    fn rescan_locked(&self, dirs: &mut HashMap<WatchDescriptor, PathBuf>) -> Result<(), SysError> {
+        let old = std::mem::take(dirs);
+        self.watch_tree(dirs, &self.root, None)?;
+        for wd in old.keys().filter(|x| !dirs.contains_key(x)) {
+            let _ = self.watcher.remove_watch(*wd);
+        }
+        Ok(())
+    }
+
+    /// 读取所有待处理的事件，没有事件时返回空列表。
+    ///
+    /// 在同一次读取中没有找到对应 `IN_MOVED_TO` 的 `IN_MOVED_FROM` 作为移出上报。
+    pub fn read_events(&self) -> Result<Vec<DirEvent>, SysError> {
+        let mut dirs = self.dirs.lock().unwrap();
+        let mut events = Vec::new();
+        // 尚未配对的移出。
+        let mut moves: Vec<(u32, PathBuf, bool)> = Vec::new();
+        for ev in self.watcher.read_events()? {
+            if ev.mask.has_q_overflow() {
+                self.rescan_locked(&mut dirs)?;
+                moves.clear();
+                events.push(DirEvent::Rescanned);
+                continue;
+            }
+            if ev.mask.has_ignored() {
+                dirs.remove(&ev.wd);
+                continue;
+            }
+            let path = match (dirs.get(&ev.wd), ev.name.as_ref()) {
+                (Some(dir), Some(name)) => dir.join(name),
+                _ => continue,
+            };
+            let is_dir = ev.mask.is_dir();
+            if ev.mask.has_moved_from() {
+                moves.push((ev.cookie, path, is_dir));
+            } else if ev.mask.has_moved_to() {
+                match moves.iter().position(|x| x.0 == ev.cookie) {
+                    Some(i) => {
+                        let (_, from, _) = moves.remove(i);
+                        if is_dir {
+                            // 目录的监视跟随 inode，只需更新路径。
+                            for dir in dirs.values_mut() {
+                                if let Ok(rest) = dir.strip_prefix(&from) {
+                                    *dir = path.join(rest);
+                                }
+                            }
+                        }
+                        events.push(DirEvent::Renamed { from, to: path });
+                    }
+                    None => {
+                        events.push(DirEvent::MovedIn(path.clone()));
+                        if is_dir {
+                            let _ = self.watch_tree(&mut dirs, &path, Some(&mut events));
+                        }
+                    }
+                }
+            } else if ev.mask.has_create() {
+                events.push(DirEvent::Created(path.clone()));
+                if is_dir {
+                    let _ = self.watch_tree(&mut dirs, &path, Some(&mut events));
+                }
+            } else if ev.mask.has_delete() {
+                events.push(DirEvent::Removed(path));
+            } else if ev.mask.has_modify() {
+                events.push(DirEvent::Modified(path));
+            }
+        }
+        for (_, path, is_dir) in moves {
+            if is_dir {
+                self.unwatch_tree(&mut dirs, &path);
+            }
+            events.push(DirEvent::MovedOut(path));
+        }
+        Ok(events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_events() {
+        const HEADER: usize = std::mem::size_of::<libc::inotify_event>();
+        let mut buf = Vec::new();
+        for (wd, mask, cookie, name) in [(1, libc::IN_CREATE, 0, &b"a.txt"[..]), (2, 4, 7, b"")] {
+            let len = if name.is_empty() { 0 } else { 16 };
+            let ev = libc::inotify_event {
+                wd,
+                mask,
+                cookie,
+                len,
+            };
+            let bytes = unsafe { std::slice::from_raw_parts(&ev as *const _ as *const u8, HEADER) };
+            buf.extend_from_slice(bytes);
+            buf.extend_from_slice(name);
+            buf.resize(buf.len() + len as usize - name.len(), 0);
+        }
+        let events = parse_events(&buf);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].wd, WatchDescriptor(1));
+        assert!(events[0].mask.has_create());
+        assert_eq!(events[0].name.as_deref(), Some(OsStr::new("a.txt")));
+        assert_eq!(events[1].cookie, 7);
+        assert_eq!(events[1].name, None);
+    }
+
+    #[test]
+    fn test_file_watcher() {
+        use crate::{Events, Poller};
+        let dir = std::env::temp_dir().join(format!("poller-inotify-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let mut poller = Poller::new().unwrap();
+        let watcher = FileWatcher::new().unwrap();
+        let mask = WatchMask::new()
+            .create()
+            .close_write()
+            .moved_from()
+            .moved_to();
+        let wd = watcher.add_watch(&dir, mask).unwrap();
+        let _reg = poller.add_fd(&watcher, Events::new().read(), None).unwrap();
+        assert!(poller.pull_events(0).unwrap().is_empty());
+        std::fs::write(dir.join("a"), b"x").unwrap();
+        std::fs::rename(dir.join("a"), dir.join("b")).unwrap();
+        assert_eq!(poller.pull_events(1000).unwrap().len(), 1);
+        let events = watcher.read_events().unwrap();
+        let masks: Vec<_> = events.iter().map(|x| x.mask).collect();
+        assert_eq!(
+            masks,
+            vec![
+                WatchMask::new().create(),
+                WatchMask::new().close_write(),
+                WatchMask::new().moved_from(),
+                WatchMask::new().moved_to(),
+            ]
+        );
+        assert!(events.iter().all(|x| x.wd == wd));
+        assert_eq!(events[2].cookie, events[3].cookie);
+        assert_eq!(events[3].name.as_deref(), Some(OsStr::new("b")));
+        watcher.remove_watch(wd).unwrap();
+        let events = watcher.read_events().unwrap();
+        assert!(events[0].mask.has_ignored());
+        assert!(watcher.remove_watch(wd).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_recursive_watcher() {
+        use crate::{Events, Poller};
+        let root = std::env::temp_dir().join(format!("poller-recursive-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&root);
+        std::fs::create_dir_all(root.join("a/b")).unwrap();
+        let mut poller = Poller::new().unwrap();
+        let watcher = RecursiveWatcher::new(&root).unwrap();
+        assert_eq!(watcher.len(), 3);
+        let _reg = poller.add_fd(&watcher, Events::new().read(), None).unwrap();
+        let wait = |n: usize| {
+            let mut events = Vec::new();
+            while events.len() < n && !poller.pull_events(1000).unwrap().is_empty() {
+                events.extend(watcher.read_events().unwrap());
+            }
+            events
+        };
+        // 新建的子目录自动添加监视。
+        std::fs::create_dir(root.join("c")).unwrap();
+        std::fs::write(root.join("c/f"), b"").unwrap();
+        std::fs::write(root.join("a/b/g"), b"").unwrap();
+        assert_eq!(
+            wait(3),
+            vec![
+                DirEvent::Created(root.join("c")),
+                DirEvent::Created(root.join("c/f")),
+                DirEvent::Created(root.join("a/b/g")),
+            ]
+        );
+        // 重命名目录后其中的路径随之更新。
+        std::fs::rename(root.join("a"), root.join("c/d")).unwrap();
+        std::fs::remove_file(root.join("c/d/b/g")).unwrap();
+        assert_eq!(
+            wait(2),
+            vec![
+                DirEvent::Renamed {
+                    from: root.join("a"),
+                    to: root.join("c/d"),
+                },
+                DirEvent::Removed(root.join("c/d/b/g")),
+            ]
+        );
+        // 移出目录树的目录不再监视。
+        let outside = root.with_extension("out");
+        std::fs::rename(root.join("c/d"), &outside).unwrap();
+        assert_eq!(wait(1), vec![DirEvent::MovedOut(root.join("c/d"))]);
+        assert_eq!(watcher.len(), 2);
+        std::fs::rename(&outside, root.join("e")).unwrap();
+        assert_eq!(
+            wait(2),
+            vec![
+                DirEvent::MovedIn(root.join("e")),
+                DirEvent::Created(root.join("e/b")),
+            ]
+        );
+        assert_eq!(watcher.len(), 4);
+        watcher.rescan().unwrap();
+        assert_eq!(watcher.len(), 4);
+        std::fs::remove_dir_all(&root).unwrap();
+    }
+}
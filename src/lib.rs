@@ -1,4 +1,8 @@
-﻿/// 定时事件枚举。
+﻿use std::any::Any;
+use std::sync::Arc;
+use std::time::Duration;
+
+/// 定时事件枚举。
 #[derive(Copy, Clone, Debug, PartialEq, Eq)]
 pub enum Event {
     /// 没有事件。
@@ -15,6 +19,16 @@ pub enum Event {
     HangUp,
     /// 单次触发。
     OneShot,
+    /// 紧急数据到达。
+    Priority,
+    /// 对端关闭写入。
+    ReadHangUp,
+    /// 独占唤醒。
+    Exclusive,
+    /// 阻止系统休眠。
+    WakeUp,
+    /// 定时器到期。
+    Timeout,
 }
 
 /// 定义事件集合。
@@ -37,7 +51,7 @@ impl std::fmt::Display for Events {
 impl Events {
     /// 创建一个新的事件集合。
     pub fn new() -> Self {
-        Self { 0: 0 }
+        Self(0)
     }
 
     /// 清空当前值且返回一个空事件集合。
@@ -64,6 +78,54 @@ impl Events {
         self
     }
 
+    /// 附加已经挂起事件到集合中。
+    pub fn hang_up(mut self) -> Self {
+        self.0 |= 1 << Event::HangUp as u32;
+        self
+    }
+
+    /// 附加边沿触发标志到集合中。
+    pub fn edge_triggered(mut self) -> Self {
+        self.0 |= 1 << Event::EdgeTriggered as u32;
+        self
+    }
+
+    /// 附加单次触发标志到集合中。
+    pub fn one_shot(mut self) -> Self {
+        self.0 |= 1 << Event::OneShot as u32;
+        self
+    }
+
+    /// 附加紧急数据到达事件到集合中。
+    pub fn priority(mut self) -> Self {
+        self.0 |= 1 << Event::Priority as u32;
+        self
+    }
+
+    /// 附加对端关闭写入事件到集合中。
+    pub fn read_hang_up(mut self) -> Self {
+        self.0 |= 1 << Event::ReadHangUp as u32;
+        self
+    }
+
+    /// 附加独占唤醒标志到集合中。
+    pub fn exclusive(mut self) -> Self {
+        self.0 |= 1 << Event::Exclusive as u32;
+        self
+    }
+
+    /// 附加阻止系统休眠标志到集合中。
+    pub fn wake_up(mut self) -> Self {
+        self.0 |= 1 << Event::WakeUp as u32;
+        self
+    }
+
+    /// 附加定时器到期事件到集合中。
+    pub fn timeout(mut self) -> Self {
+        self.0 |= 1 << Event::Timeout as u32;
+        self
+    }
+
     /// 检查集合是否为空。
     pub fn is_none(self) -> bool {
         self.0 == 0
@@ -83,6 +145,46 @@ impl Events {
     pub fn has_error(self) -> bool {
         (self.0 & (1 << Event::Error as u32)) != 0
     }
+
+    /// 检查集合是否有已经挂起事件。
+    pub fn has_hang_up(self) -> bool {
+        (self.0 & (1 << Event::HangUp as u32)) != 0
+    }
+
+    /// 检查集合是否有边沿触发标志。
+    pub fn has_edge_triggered(self) -> bool {
+        (self.0 & (1 << Event::EdgeTriggered as u32)) != 0
+    }
+
+    /// 检查集合是否有单次触发标志。
+    pub fn has_one_shot(self) -> bool {
+        (self.0 & (1 << Event::OneShot as u32)) != 0
+    }
+
+    /// 检查集合是否有紧急数据到达事件。
+    pub fn has_priority(self) -> bool {
+        (self.0 & (1 << Event::Priority as u32)) != 0
+    }
+
+    /// 检查集合是否有对端关闭写入事件。
+    pub fn has_read_hang_up(self) -> bool {
+        (self.0 & (1 << Event::ReadHangUp as u32)) != 0
+    }
+
+    /// 检查集合是否有独占唤醒标志。
+    pub fn has_exclusive(self) -> bool {
+        (self.0 & (1 << Event::Exclusive as u32)) != 0
+    }
+
+    /// 检查集合是否有阻止系统休眠标志。
+    pub fn has_wake_up(self) -> bool {
+        (self.0 & (1 << Event::WakeUp as u32)) != 0
+    }
+
+    /// 检查集合是否有定时器到期事件。
+    pub fn has_timeout(self) -> bool {
+        (self.0 & (1 << Event::Timeout as u32)) != 0
+    }
 }
 
 /// 定义系统错误。
@@ -99,36 +201,187 @@ impl std::error::Error for SysError {}
 
 impl From<i32> for SysError {
     fn from(val: i32) -> Self {
-        Self { 0: val }
+        Self(val)
     }
 }
 
-impl Into<i32> for SysError {
-    fn into(self) -> i32 {
-        self.0
+impl From<SysError> for i32 {
+    fn from(val: SysError) -> Self {
+        val.0
     }
 }
 
 impl SysError {
     /// 从系统当前 errno 创建一个 SysError 对象。
     pub fn last() -> Self {
-        unsafe {
-            Self {
-                0: *(libc::__errno_location()),
-            }
+        Self(std::io::Error::last_os_error().raw_os_error().unwrap_or(0))
+    }
+}
+
+/// 定义事件关联上下文。
+pub type EventContext = Arc<dyn Any + Send + Sync>;
+
+/// 定义注册令牌。
+///
+/// 由槽位索引及代数组成，唯一标识 `Poller` 中的一次注册；注册移除后槽位虽会被复用，
+/// 但代数不同，因此旧的令牌不会与之后复用相同编号 `fd` 的注册混淆。
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Token(u64);
+
+impl Token {
+    pub(crate) fn new(index: usize, generation: u32) -> Self {
+        Self((generation as u64) << 32 | index as u64)
+    }
+
+    pub(crate) fn index(self) -> usize {
+        (self.0 & 0xffff_ffff) as usize
+    }
+
+    pub(crate) fn generation(self) -> u32 {
+        (self.0 >> 32) as u32
+    }
+}
+
+impl From<Token> for u64 {
+    fn from(val: Token) -> Self {
+        val.0
+    }
+}
+
+/// 定义事件数据。
+///
+/// # Fields
+/// * `0` - 触发的文件描述符。
+/// * `1` - 触发的事件集合。
+/// * `2` - 触发的事件对应上下文。
+/// * `3` - 触发的事件对应注册令牌。
+pub type EventData<'a, T = EventContext> = (i32, Events, Option<&'a T>, Token);
+
+/// 定义后端原始事件。
+///
+/// # Fields
+/// * `0` - 注册时关联的用户数据。
+/// * `1` - 触发的事件集合。
+pub type RawEvent = (u64, Events);
+
+/// 定义 I/O 事件通知后端。
+///
+/// `Poller` 通过此接口管理文件描述符及等待事件，除内置的 `Epoll`、`Poll`、`Select` 外，
+/// 也可以由用户自行实现（例如测试中注入的模拟后端）。
+pub trait Backend: std::fmt::Debug + Send + Sync {
+    /// 注册一个文件描述符，`data` 会在事件触发时原样返回。
+    fn register(&self, fd: i32, events: Events, data: u64) -> Result<(), SysError>;
+
+    /// 修改一个已注册文件描述符的监视事件及关联数据。
+    fn modify(&self, fd: i32, events: Events, data: u64) -> Result<(), SysError>;
+
+    /// 注销一个文件描述符。
+    fn deregister(&self, fd: i32) -> Result<(), SysError>;
+
+    /// 等待事件并填充到 `events` 中，返回实际填充的事件数量。
+    ///
+    /// 每次最多填充 `events.len()` 个事件，`timeout` 为 `None` 时表示无限等待。
+    fn wait(&self, events: &mut [RawEvent], timeout: Option<Duration>) -> Result<usize, SysError>;
+
+    /// 等待事件，等待期间原子地将当前线程的信号掩码替换为 `sigmask`。
+    ///
+    /// 不支持此功能的后端返回 `ENOSYS`。
+    fn wait_with_sigmask(
+        &self,
+        _events: &mut [RawEvent],
+        _timeout: Option<Duration>,
+        _sigmask: &SigSet,
+    ) -> Result<usize, SysError> {
+        Err(SysError::from(libc::ENOSYS))
+    }
+
+    /// 返回内核中的注册项，供 `Poller::audit` 核对。
+    ///
+    /// 注册信息只保存在用户空间的后端返回 `ENOSYS`。
+    fn entries(&self) -> Result<Vec<KernelEntry>, SysError> {
+        Err(SysError::from(libc::ENOSYS))
+    }
+}
+
+/// 定义内核中的注册项。
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct KernelEntry {
+    /// 注册的文件描述符。
+    pub fd: i32,
+    /// 监视的事件集合。
+    pub events: Events,
+    /// 注册时关联的用户数据。
+    pub data: u64,
+    /// 注册的文件的 inode 编号。
+    pub ino: u64,
+    /// 注册的文件所在的设备编号，与 `stat` 的 `st_dev` 编码相同。
+    pub dev: u64,
+}
+
+/// 将毫秒超时转换为 `Duration` 超时，负数表示无限等待。
+pub(crate) fn timeout_from_ms(timeout_ms: i32) -> Option<Duration> {
+    if timeout_ms < 0 {
+        None
+    } else {
+        Some(Duration::from_millis(timeout_ms as u64))
+    }
+}
+
+/// 将 `Duration` 超时向上取整为毫秒，`None` 转换为 -1。
+pub(crate) fn timeout_to_ms(timeout: Option<Duration>) -> i32 {
+    match timeout {
+        Some(d) => {
+            let ms = d.as_nanos().div_ceil(1_000_000);
+            ms.min(i32::MAX as u128) as i32
         }
+        None => -1,
+    }
+}
+
+/// 将 `Duration` 转换为 `timespec`。
+pub(crate) fn to_timespec(d: Duration) -> libc::timespec {
+    libc::timespec {
+        tv_sec: d.as_secs().min(libc::time_t::MAX as u64) as libc::time_t,
+        tv_nsec: d.subsec_nanos() as _,
     }
 }
 
 #[cfg(target_os = "linux")]
 pub mod epoll;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod follow;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod inotify;
+mod list;
+pub mod poll;
+mod poller;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod process;
+pub mod select;
+pub mod signal;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+pub mod timer;
+mod waker;
+mod wheel;
 
-#[cfg(target_os = "linux")]
-#[doc(inline)]
-pub use epoll::{EventContext, EventData, Poller};
+use signal::SigSet;
 
-#[cfg(not(target_os = "linux"))]
-pub mod select;
+#[doc(inline)]
+pub use poller::{AuditIssue, EventBuffer, EventIter, Poller, Registration, Registry, WatchIter};
+pub use waker::Waker;
+pub use wheel::TimerId;
 
 #[cfg(test)]
-mod tests {}
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timeout_to_ms() {
+        assert_eq!(timeout_to_ms(None), -1);
+        assert_eq!(timeout_to_ms(Some(Duration::from_micros(250))), 1);
+        assert_eq!(timeout_to_ms(Some(Duration::from_millis(2))), 2);
+        assert_eq!(timeout_to_ms(Some(Duration::from_secs(u64::MAX))), i32::MAX);
+        assert_eq!(timeout_from_ms(-1), None);
+        assert_eq!(timeout_from_ms(5), Some(Duration::from_millis(5)));
+    }
+}
@@ -0,0 +1,191 @@
+//! `poll` 与 `select` 后端共用的监视列表。
+//!
+use crate::{Events, RawEvent, SysError, Waker};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// 定义监视项。
+#[derive(Debug)]
+struct Entry {
+    fd: i32,
+    events: Events,
+    data: u64,
+    armed: bool,
+}
+
+/// 定义等待前复制的监视项，依次为文件描述符、事件集合及关联数据。
+pub(crate) type Snapshot = (i32, Events, u64);
+
+/// 定义就绪的监视项，依次为文件描述符、关联数据及触发的事件集合。
+pub(crate) type Ready = (i32, u64, Events);
+
+/// 定义在用户空间保存的监视列表。
+///
+/// 不支持边沿触发，并模拟单次触发；等待期间其他线程仍可修改，修改后通过唤醒器中断等待。
+#[derive(Debug, Default)]
+pub(crate) struct WatchList {
+    entries: Mutex<Vec<Entry>>,
+    /// 监视列表发生变化时中断正在进行的等待，首次等待时创建。
+    waker: Mutex<Option<Waker>>,
+    /// 文件描述符编号的上限，为 `None` 时不限制。
+    limit: Option<usize>,
+}
+
+impl WatchList {
+    /// 创建一个文件描述符编号必须小于 `limit` 的监视列表。
+    pub(crate) fn with_limit(limit: usize) -> Self {
+        Self {
+            limit: Some(limit),
+            ..Self::default()
+        }
+    }
+
+    fn in_limit(&self, fd: i32) -> bool {
+        self.limit.is_none_or(|x| (fd as usize) < x)
+    }
+
+    /// 返回中断等待用的唤醒器，创建失败或编号超出上限时返回 `None`，
+    /// 此时其他线程的修改要到下次等待才会生效。
+    fn waker(&self) -> Option<Waker> {
+        let mut waker = self.waker.lock().unwrap();
+        if waker.is_none() {
+            *waker = Waker::new().ok().filter(|x| self.in_limit(x.fd()));
+        }
+        waker.clone()
+    }
+
+    /// 通知正在等待的线程重新读取监视列表。
+    fn notify(&self) {
+        if let Some(waker) = self.waker.lock().unwrap().as_ref() {
+            let _ = waker.wake();
+        }
+    }
+
+    pub(crate) fn register(&self, fd: i32, events: Events, data: u64) -> Result<(), SysError> {
+        // 与 epoll_ctl(2) 保持一致，拒绝无效的文件描述符。
+        if fd < 0 || unsafe { libc::fcntl(fd, libc::F_GETFD) } < 0 {
+            return Err(SysError::from(libc::EBADF));
+        }
+        if !self.in_limit(fd) || events.has_edge_triggered() {
+            return Err(SysError::from(libc::EINVAL));
+        }
+        let mut entries = self.entries.lock().unwrap();
+        if entries.iter().any(|x| x.fd == fd) {
+            return Err(SysError::from(libc::EEXIST));
+        }
+        entries.push(Entry {
+            fd,
+            events,
+            data,
+            armed: true,
+        });
+        drop(entries);
+        self.notify();
+        Ok(())
+    }
+
+    pub(crate) fn modify(&self, fd: i32, events: Events, data: u64) -> Result<(), SysError> {
+        if events.has_edge_triggered() {
+            return Err(SysError::from(libc::EINVAL));
+        }
+        let mut entries = self.entries.lock().unwrap();
+        match entries.iter_mut().find(|x| x.fd == fd) {
+            Some(x) => {
+                x.events = events;
+                x.data = data;
+                x.armed = true;
+            }
+            None => return Err(SysError::from(libc::ENOENT)),
+        }
+        drop(entries);
+        self.notify();
+        Ok(())
+    }
+
+    pub(crate) fn deregister(&self, fd: i32) -> Result<(), SysError> {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.iter().position(|x| x.fd == fd) {
+            Some(i) => {
+                entries.swap_remove(i);
+            }
+            None => return Err(SysError::from(libc::ENOENT)),
+        }
+        drop(entries);
+        self.notify();
+        Ok(())
+    }
+
+    /// 等待事件并填充到 `events` 中，返回实际填充的事件数量。
+    ///
+    /// `f` 使用复制的启用中的监视项、唤醒器的文件描述符及超时执行一次等待，
+    /// 返回就绪的监视项（文件描述符、关联数据及事件集合）以及唤醒器是否就绪。
+    pub(crate) fn wait<F>(
+        &self,
+        events: &mut [RawEvent],
+        timeout: Option<Duration>,
+        mut f: F,
+    ) -> Result<usize, SysError>
+    where
+        F: FnMut(
+            &[Snapshot],
+            Option<i32>,
+            Option<Duration>,
+        ) -> Result<(Vec<Ready>, bool), SysError>,
+    {
+        let waker = self.waker();
+        let deadline = timeout.and_then(|d| Instant::now().checked_add(d));
+        let mut timeout = timeout;
+        loop {
+            // 复制一份监视列表，等待期间其他线程仍可修改，修改后通过唤醒器中断等待重新复制。
+            let snapshot: Vec<Snapshot> = self
+                .entries
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|x| x.armed)
+                .map(|x| (x.fd, x.events, x.data))
+                .collect();
+            let (ready, woken) = f(&snapshot, waker.as_ref().map(|x| x.fd()), timeout)?;
+            if woken {
+                if let Some(waker) = waker.as_ref() {
+                    waker.drain();
+                }
+            } else if ready.is_empty() {
+                return Ok(0);
+            }
+            let n = self.report(ready, events);
+            if n > 0 {
+                return Ok(n);
+            }
+            // 只有唤醒器就绪或就绪的监视项已被注销时继续等待，直到超时。
+            if let Some(deadline) = deadline {
+                let now = Instant::now();
+                if now >= deadline {
+                    return Ok(0);
+                }
+                timeout = Some(deadline - now);
+            }
+        }
+    }
+
+    /// 将就绪的监视项填充到 `events` 中。
+    fn report(&self, ready: Vec<Ready>, events: &mut [RawEvent]) -> usize {
+        let mut n = 0;
+        let mut entries = self.entries.lock().unwrap();
+        for (fd, data, ev) in ready {
+            if n >= events.len() {
+                break;
+            }
+            // 等待期间可能已被其他线程注销或上报，只上报仍处于启用状态的监视项。
+            if let Some(x) = entries
+                .iter_mut()
+                .find(|x| x.fd == fd && x.data == data && x.armed)
+            {
+                events[n] = (data, ev);
+                n += 1;
+                x.armed = !x.events.has_one_shot();
+            }
+        }
+        n
+    }
+}
@@ -0,0 +1,187 @@
+//! 基于 `poll(2)` 的 I/O 事件通知。
+//!
+//! 在非 Linux 平台上默认使用此后端，在 Linux 平台上可通过 `poll` 特性设为默认后端。
+//!
+//! `poll(2)` 不支持边沿触发，注册时携带 `Event::EdgeTriggered` 会返回 `EINVAL`；
+//! `Event::OneShot` 由本后端模拟，事件上报后自动停止监视直到再次 `modify`。
+use crate::list::WatchList;
+use crate::signal::SigSet;
+use crate::{Backend, Events, RawEvent, SysError};
+use libc::{c_int, c_short, nfds_t, pollfd};
+use std::time::Duration;
+
+/// 等待事件，支持 `ppoll(2)` 的平台上使用纳秒精度。
+#[cfg(any(target_os = "linux", target_os = "android", target_os = "freebsd"))]
+fn poll(
+    fds: &mut [pollfd],
+    timeout: Option<Duration>,
+    sigmask: Option<&SigSet>,
+) -> Result<c_int, SysError> {
+    let ts = timeout.map(crate::to_timespec);
+    let pts = ts
+        .as_ref()
+        .map_or(std::ptr::null(), |x| x as *const libc::timespec);
+    let pmask = sigmask.map_or(std::ptr::null(), |x| x.as_ptr());
+    match unsafe { libc::ppoll(fds.as_mut_ptr(), fds.len() as nfds_t, pts, pmask) } {
+        n if n < 0 => Err(SysError::last()),
+        n => Ok(n),
+    }
+}
+
+/// 等待事件，超时向上取整到毫秒，不支持信号掩码。
+#[cfg(not(any(target_os = "linux", target_os = "android", target_os = "freebsd")))]
+fn poll(
+    fds: &mut [pollfd],
+    timeout: Option<Duration>,
+    sigmask: Option<&SigSet>,
+) -> Result<c_int, SysError> {
+    if sigmask.is_some() {
+        return Err(SysError::from(libc::ENOSYS));
+    }
+    let timeout_ms = crate::timeout_to_ms(timeout);
+    match unsafe { libc::poll(fds.as_mut_ptr(), fds.len() as nfds_t, timeout_ms) } {
+        n if n < 0 => Err(SysError::last()),
+        n => Ok(n),
+    }
+}
+
+/// 将事件集合转换为 `poll(2)` 的事件掩码。
+fn to_poll_events(events: Events) -> c_short {
+    let mut bits: c_short = 0;
+    if events.has_read() {
+        bits |= libc::POLLIN;
+    }
+    if events.has_write() {
+        bits |= libc::POLLOUT;
+    }
+    if events.has_priority() {
+        bits |= libc::POLLPRI;
+    }
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if events.has_read_hang_up() {
+        bits |= libc::POLLRDHUP;
+    }
+    bits
+}
+
+/// 将 `poll(2)` 返回的事件掩码转换为事件集合。
+fn from_poll_events(bits: c_short) -> Events {
+    let mut events = Events::new();
+    if (bits & libc::POLLIN) == libc::POLLIN {
+        events = events.read();
+    }
+    if (bits & libc::POLLOUT) == libc::POLLOUT {
+        events = events.write();
+    }
+    if (bits & (libc::POLLERR | libc::POLLNVAL)) != 0 {
+        events = events.error();
+    }
+    if (bits & libc::POLLHUP) == libc::POLLHUP {
+        events = events.hang_up();
+    }
+    if (bits & libc::POLLPRI) == libc::POLLPRI {
+        events = events.priority();
+    }
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    if (bits & libc::POLLRDHUP) == libc::POLLRDHUP {
+        events = events.read_hang_up();
+    }
+    events
+}
+
+/// 基于 `poll(2)` 的后端。
+#[derive(Debug, Default)]
+pub struct Poll {
+    list: WatchList,
+}
+
+impl Poll {
+    /// 创建一个新的 `poll(2)` 后端。
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Backend for Poll {
+    fn register(&self, fd: i32, events: Events, data: u64) -> Result<(), SysError> {
+        self.list.register(fd, events, data)
+    }
+
+    fn modify(&self, fd: i32, events: Events, data: u64) -> Result<(), SysError> {
+        self.list.modify(fd, events, data)
+    }
+
+    fn deregister(&self, fd: i32) -> Result<(), SysError> {
+        self.list.deregister(fd)
+    }
+
+    fn wait(&self, events: &mut [RawEvent], timeout: Option<Duration>) -> Result<usize, SysError> {
+        self.wait_events(events, timeout, None)
+    }
+
+    fn wait_with_sigmask(
+        &self,
+        events: &mut [RawEvent],
+        timeout: Option<Duration>,
+        sigmask: &SigSet,
+    ) -> Result<usize, SysError> {
+        self.wait_events(events, timeout, Some(sigmask))
+    }
+}
+
+impl Poll {
+    fn wait_events(
+        &self,
+        events: &mut [RawEvent],
+        timeout: Option<Duration>,
+        sigmask: Option<&SigSet>,
+    ) -> Result<usize, SysError> {
+        self.list
+            .wait(events, timeout, |snapshot, waker_fd, timeout| {
+                let mut fds: Vec<pollfd> = snapshot
+                    .iter()
+                    .map(|&(fd, events, _)| pollfd {
+                        fd,
+                        events: to_poll_events(events),
+                        revents: 0,
+                    })
+                    .collect();
+                if let Some(fd) = waker_fd {
+                    fds.push(pollfd {
+                        fd,
+                        events: libc::POLLIN,
+                        revents: 0,
+                    });
+                }
+                poll(&mut fds, timeout, sigmask)?;
+                let woken = waker_fd.is_some() && fds.pop().is_some_and(|x| x.revents != 0);
+                let ready = fds
+                    .iter()
+                    .zip(snapshot)
+                    .filter(|x| x.0.revents != 0)
+                    .map(|(pfd, &(fd, _, data))| (fd, data, from_poll_events(pfd.revents)))
+                    .collect();
+                Ok((ready, woken))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_poll_events() {
+        let events = Events::new().read().write();
+        assert_eq!(
+            from_poll_events(to_poll_events(events)),
+            Events::new().read().write()
+        );
+        assert!(from_poll_events(libc::POLLNVAL).has_error());
+        assert!(from_poll_events(libc::POLLHUP).has_hang_up());
+        assert_eq!(
+            from_poll_events(to_poll_events(Events::new().priority())),
+            Events::new().priority()
+        );
+    }
+}
@@ -0,0 +1,2150 @@
+//! 文件 I/O 事件通知器。
+//!
+use crate::signal::SigSet;
+use crate::wheel::{TimerId, Wheel, TIMER_FLAG};
+use crate::{
+    timeout_from_ms, Backend, EventContext, EventData, Events, KernelEntry, RawEvent, SysError,
+    Token, Waker,
+};
+use std::collections::HashMap;
+use std::marker::PhantomData;
+use std::os::unix::io::{AsFd, AsRawFd, IntoRawFd, OwnedFd};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
+use std::time::{Duration, Instant};
+
+/// 创建当前平台默认的后端。
+#[cfg(all(target_os = "linux", not(feature = "poll")))]
+fn default_backend() -> Result<Box<dyn Backend>, SysError> {
+    Ok(Box::new(crate::epoll::Epoll::new()?))
+}
+
+/// 创建当前平台默认的后端。
+#[cfg(any(not(target_os = "linux"), feature = "poll"))]
+fn default_backend() -> Result<Box<dyn Backend>, SysError> {
+    Ok(Box::new(crate::poll::Poll::new()))
+}
+
+/// 默认每次最多拉取的事件数量。
+const DEFAULT_MAX_EVENTS: usize = 64;
+
+/// 唤醒器注册时关联的数据，不会与任何 `Token` 相同。
+const WAKER_DATA: u64 = u64::MAX;
+
+/// 定义监视项。
+#[derive(Debug)]
+struct Watch {
+    fd: i32,
+    /// 监视的事件集合。
+    events: AtomicU32,
+    /// 单次触发的监视项在上报事件后变为未启用，需调用 `rearm` 重新启用。
+    armed: AtomicBool,
+    /// 由 `Poller` 持有所有权的 `fd`，监视项移除时一并关闭。
+    owner: Option<OwnedFd>,
+}
+
+impl Watch {
+    fn new(fd: i32, events: Events, owner: Option<OwnedFd>) -> Self {
+        Self {
+            fd,
+            events: AtomicU32::new(events.0),
+            armed: AtomicBool::new(true),
+            owner,
+        }
+    }
+
+    fn events(&self) -> Events {
+        Events(self.events.load(Ordering::Acquire))
+    }
+
+    fn is_armed(&self) -> bool {
+        self.armed.load(Ordering::Acquire)
+    }
+}
+
+/// 定义监视项槽位，每次释放后代数加一，使旧的 `Token` 失效。
+#[derive(Debug)]
+struct Slot {
+    generation: u32,
+    watch: Option<Watch>,
+}
+
+/// 定义监视列表。
+#[derive(Debug, Default)]
+struct Slab {
+    /// 以 `Token` 的索引访问的监视项。
+    slots: Vec<Slot>,
+    /// 空闲的槽位索引。
+    free: Vec<usize>,
+    /// 文件描述符到 `Token` 的索引，用于以 `fd` 为参数的操作。
+    fds: HashMap<i32, Token>,
+    /// `Poller` 已被销毁。
+    closed: bool,
+}
+
+impl Slab {
+    fn get(&self, token: Token) -> Option<&Watch> {
+        self.slots
+            .get(token.index())
+            .filter(|x| x.generation == token.generation())
+            .and_then(|x| x.watch.as_ref())
+    }
+
+    fn get_mut(&mut self, token: Token) -> Option<&mut Watch> {
+        self.slots
+            .get_mut(token.index())
+            .filter(|x| x.generation == token.generation())
+            .and_then(|x| x.watch.as_mut())
+    }
+
+    /// 查找一个文件描述符对应的监视项。
+    fn find(&self, fd: i32) -> Option<(Token, &Watch)> {
+        let token = *self.fds.get(&fd)?;
+        self.get(token).map(|v| (token, v))
+    }
+
+    /// 返回下一个插入的监视项将使用的 `Token`。
+    fn vacant(&self) -> Token {
+        let index = self.free.last().copied().unwrap_or(self.slots.len());
+        let generation = self.slots.get(index).map_or(0, |x| x.generation);
+        Token::new(index, generation)
+    }
+
+    /// 在 `vacant` 返回的位置插入监视项，返回被替换的同一 `fd` 的旧 `Token`。
+    fn insert(&mut self, watch: Watch) -> Option<Token> {
+        let token = self.vacant();
+        if self.free.pop().is_none() {
+            self.slots.push(Slot {
+                generation: 0,
+                watch: None,
+            });
+        }
+        // 后端接受了注册说明原来相同编号的 `fd` 已被关闭，丢弃遗留的监视项。
+        let old = self.fds.insert(watch.fd, token);
+        self.slots[token.index()].watch = Some(watch);
+        let old = old?;
+        let mut watch = self.remove(old)?;
+        // 编号现在属于新注册的文件，不能再由原来的所有者关闭。
+        if let Some(owner) = watch.owner.take() {
+            let _ = owner.into_raw_fd();
+        }
+        Some(old)
+    }
+
+    /// 移除监视项并释放所在的槽位。
+    fn remove(&mut self, token: Token) -> Option<Watch> {
+        let slot = self
+            .slots
+            .get_mut(token.index())
+            .filter(|x| x.generation == token.generation())?;
+        let watch = slot.watch.take()?;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(token.index());
+        if self.fds.get(&watch.fd) == Some(&token) {
+            self.fds.remove(&watch.fd);
+        }
+        Some(watch)
+    }
+}
+
+/// 定义 `Poller` 与 `Registry`、`Registration` 共享的状态。
+#[derive(Debug)]
+struct Shared {
+    backend: Box<dyn Backend>,
+    slab: RwLock<Slab>,
+    /// 已在 `Poller` 以外移除、上下文尚未回收的监视项。
+    garbage: Mutex<Vec<Token>>,
+}
+
+impl Shared {
+    fn slab(&self) -> RwLockReadGuard<'_, Slab> {
+        self.slab.read().unwrap()
+    }
+
+    fn add(&self, watch: Watch) -> Result<Token, SysError> {
+        let mut slab = self.slab.write().unwrap();
+        if slab.closed {
+            return Err(SysError::from(libc::EBADF));
+        }
+        let token = slab.vacant();
+        if token.index() & TIMER_FLAG != 0 {
+            return Err(SysError::from(libc::ENOSPC));
+        }
+        self.backend.register(watch.fd, watch.events(), token.0)?;
+        if let Some(old) = slab.insert(watch) {
+            self.garbage.lock().unwrap().push(old);
+        }
+        Ok(token)
+    }
+
+    /// 移除一个监视项，`Token` 已失效时返回 `ENOENT`。
+    ///
+    /// 后端注销失败时监视项仍会被移除，例如 `fd` 已经被关闭。
+    fn remove(&self, token: Token) -> Result<(), SysError> {
+        let mut slab = self.slab.write().unwrap();
+        let fd = match slab.get(token) {
+            Some(v) => v.fd,
+            None => return Err(SysError::from(libc::ENOENT)),
+        };
+        let r = self.backend.deregister(fd);
+        slab.remove(token);
+        r
+    }
+
+    /// 移除一个文件描述符，返回其 `Token` 及后端注销的结果，未被监视时返回 `ENOENT`。
+    ///
+    /// 与 `remove` 一样，后端注销失败时监视项仍会被移除。
+    fn remove_fd(&self, fd: i32) -> Result<(Token, Result<(), SysError>), SysError> {
+        let mut slab = self.slab.write().unwrap();
+        let token = match slab.find(fd) {
+            Some((token, _)) => token,
+            None => return Err(SysError::from(libc::ENOENT)),
+        };
+        let r = self.backend.deregister(fd);
+        slab.remove(token);
+        Ok((token, r))
+    }
+
+    /// 修改监视的事件集合并重新启用，`events` 为 `None` 时使用已保存的事件集合。
+    fn modify(&self, token: Token, events: Option<Events>) -> Result<(), SysError> {
+        // 持有写锁直到保存完成，避免并发修改在内核与监视项中的生效顺序不一致。
+        let mut slab = self.slab.write().unwrap();
+        match slab.get_mut(token) {
+            Some(v) => {
+                let events = events.unwrap_or_else(|| v.events());
+                self.backend.modify(v.fd, events, token.0)?;
+                *v.events.get_mut() = events.0;
+                *v.armed.get_mut() = true;
+                Ok(())
+            }
+            None => Err(SysError::from(libc::ENOENT)),
+        }
+    }
+
+    /// 修改一个文件描述符，返回其 `Token`。
+    fn modify_fd(&self, fd: i32, events: Option<Events>) -> Result<Token, SysError> {
+        let token = self.token(fd).ok_or_else(|| SysError::from(libc::ENOENT))?;
+        self.modify(token, events)?;
+        Ok(token)
+    }
+
+    fn token(&self, fd: i32) -> Option<Token> {
+        self.slab().find(fd).map(|(token, _)| token)
+    }
+
+    fn add_fd<'a, F: AsFd + ?Sized>(
+        self: &Arc<Self>,
+        source: &'a F,
+        events: Events,
+    ) -> Result<Registration<'a>, SysError> {
+        let fd = source.as_fd().as_raw_fd();
+        let token = self.add(Watch::new(fd, events, None))?;
+        Ok(Registration::new(self, fd, token))
+    }
+
+    fn register(
+        self: &Arc<Self>,
+        fd: i32,
+        events: Events,
+    ) -> Result<Registration<'static>, SysError> {
+        let token = self.add(Watch::new(fd, events, None))?;
+        Ok(Registration::new(self, fd, token))
+    }
+
+    fn add_owned<F: Into<OwnedFd>>(&self, source: F, events: Events) -> Result<Token, SysError> {
+        let owner = source.into();
+        self.add(Watch::new(owner.as_raw_fd(), events, Some(owner)))
+    }
+}
+
+/// 定义以 `Token` 的索引访问的上下文，代数与 `Token` 不同的已失效。
+#[derive(Debug)]
+struct Contexts<T>(Vec<Option<(u32, T)>>);
+
+impl<T> Contexts<T> {
+    fn set(&mut self, token: Token, ctx: Option<T>) {
+        if self.0.len() <= token.index() {
+            self.0.resize_with(token.index() + 1, || None);
+        }
+        self.0[token.index()] = ctx.map(|x| (token.generation(), x));
+    }
+
+    fn free(&mut self, token: Token) {
+        if let Some(x) = self.0.get_mut(token.index()) {
+            if x.as_ref().is_some_and(|x| x.0 == token.generation()) {
+                *x = None;
+            }
+        }
+    }
+
+    fn get(&self, token: Token) -> Option<&T> {
+        match self.0.get(token.index()) {
+            Some(Some((generation, ctx))) if *generation == token.generation() => Some(ctx),
+            _ => None,
+        }
+    }
+
+    fn get_mut(&mut self, token: Token) -> Option<&mut T> {
+        match self.0.get_mut(token.index()) {
+            Some(Some((generation, ctx))) if *generation == token.generation() => Some(ctx),
+            _ => None,
+        }
+    }
+}
+
+/// 定义文件 I/O 事件通知器。
+///
+/// 每个实例可以管理多个 `fd` 的 I/O 事件，每个监视项可以关联一个 `T` 类型的上下文，
+/// 默认为 `EventContext`。
+///
+/// # Examples
+///
+/// ```
+/// use poller::{Events, Poller};
+/// let mut poller = Poller::<String>::new_typed().unwrap();
+/// poller.add(1, Events::new().write(), Some("stdout".to_string())).unwrap();
+/// let events = poller.pull_events(1000).unwrap();
+/// assert_eq!(events[0].2.map(|x| x.as_str()), Some("stdout"));
+/// ```
+#[derive(Debug)]
+pub struct Poller<T = EventContext> {
+    shared: Arc<Shared>,
+    ctxs: Contexts<T>,
+    /// 软件定时器，等待时不会超过下一个定时器的到期时间。
+    wheel: Mutex<Wheel>,
+    timer_ctxs: Contexts<T>,
+    max_events: usize,
+    retry_on_interrupt: bool,
+    audit_on_add: bool,
+    waker: Option<Waker>,
+}
+
+impl<T> Drop for Poller<T> {
+    fn drop(&mut self) {
+        // `Registry` 可能比 `Poller` 活得更久，移除所有监视项并关闭持有所有权的 `fd`。
+        let mut slab = self.shared.slab.write().unwrap();
+        slab.closed = true;
+        for slot in slab.slots.iter_mut() {
+            if let Some(v) = slot.watch.take() {
+                let _ = self.shared.backend.deregister(v.fd);
+            }
+        }
+        slab.fds.clear();
+    }
+}
+
+impl Poller {
+    /// 使用当前平台默认的后端创建一个新的 I/O 事件通知器。
+    ///
+    /// 在 Linux 平台上默认使用 `epoll`，启用 `poll` 特性或在其他平台上使用 `poll(2)`。
+    pub fn new() -> Result<Self, SysError> {
+        Ok(Self::with_backend(default_backend()?))
+    }
+
+    /// 使用指定的后端创建一个新的 I/O 事件通知器。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use poller::{poll::Poll, Poller};
+    /// let poller = Poller::with_backend(Box::new(Poll::new()));
+    /// ```
+    pub fn with_backend(backend: Box<dyn Backend>) -> Self {
+        Self::with_backend_typed(backend)
+    }
+}
+
+impl<T> Poller<T> {
+    /// 使用当前平台默认的后端创建一个上下文类型为 `T` 的 I/O 事件通知器。
+    pub fn new_typed() -> Result<Self, SysError> {
+        Ok(Self::with_backend_typed(default_backend()?))
+    }
+
+    /// 使用指定的后端创建一个上下文类型为 `T` 的 I/O 事件通知器。
+    pub fn with_backend_typed(backend: Box<dyn Backend>) -> Self {
+        Self {
+            shared: Arc::new(Shared {
+                backend,
+                slab: RwLock::new(Slab::default()),
+                garbage: Mutex::new(Vec::new()),
+            }),
+            ctxs: Contexts(Vec::new()),
+            wheel: Mutex::new(Wheel::new()),
+            timer_ctxs: Contexts(Vec::new()),
+            max_events: DEFAULT_MAX_EVENTS,
+            retry_on_interrupt: false,
+            audit_on_add: false,
+            waker: None,
+        }
+    }
+
+    /// 设置 `pull_events` 每次最多拉取的事件数量，默认为 64，最小为 1。
+    ///
+    /// 未被拉取的事件会在下次调用时继续上报。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use poller::Poller;
+    /// let poller = Poller::new().unwrap().max_events(1024);
+    /// ```
+    pub fn max_events(mut self, max_events: usize) -> Self {
+        self.max_events = max_events.max(1);
+        self
+    }
+
+    /// 设置等待被信号中断（`EINTR`）时是否自动重试，默认不重试。
+    ///
+    /// 重试时会扣除已经等待的时间，总等待时间不会超过调用者指定的超时。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use poller::Poller;
+    /// let poller = Poller::new().unwrap().retry_on_interrupt(true);
+    /// ```
+    pub fn retry_on_interrupt(mut self, enable: bool) -> Self {
+        self.retry_on_interrupt = enable;
+        self
+    }
+
+    /// 设置每次添加文件描述符后是否自动调用 `audit`，发现问题时 panic，默认不启用。
+    ///
+    /// 仅在调试构建（`debug_assertions`）中生效，后端不支持 `audit` 时忽略。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use poller::Poller;
+    /// let poller = Poller::new().unwrap().audit_on_add(true);
+    /// ```
+    pub fn audit_on_add(mut self, enable: bool) -> Self {
+        self.audit_on_add = enable;
+        self
+    }
+
+    /// 返回可在其他线程中添加、移除文件描述符的 `Registry`。
+    pub fn registry(&self) -> Registry {
+        Registry {
+            shared: Arc::clone(&self.shared),
+        }
+    }
+
+    /// 添加一个文件描述符到监视列表中，返回标识此次注册的 `Token`。
+    ///
+    /// **注意：** 此函数不会把 `fd` 的所有权转移到 `Poller` 内，请确保在 `Poller` 活动期内 `fd` 都是可用的。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use poller::{Events, Poller};
+    /// let mut poller = Poller::new().unwrap();
+    /// let token = poller.add(1, Events::new().write(), None).unwrap();
+    /// assert_eq!(poller.pull_events(1000).unwrap()[0].3, token);
+    /// ```
+    pub fn add(&mut self, fd: i32, events: Events, ctx: Option<T>) -> Result<Token, SysError> {
+        self.collect_garbage();
+        let token = self.shared.add(Watch::new(fd, events, None))?;
+        self.inserted(token, ctx);
+        Ok(token)
+    }
+
+    /// 添加一个文件描述符来源到监视列表中，返回的 `Registration` 销毁时自动移除。
+    ///
+    /// `Registration` 的生命周期不会超过 `source`，因此不会在监视列表中遗留已关闭的 `fd`。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use poller::{Events, Poller};
+    /// use std::os::unix::net::UnixStream;
+    /// let mut poller = Poller::new().unwrap();
+    /// let (a, _b) = UnixStream::pair().unwrap();
+    /// let reg = poller.add_fd(&a, Events::new().write(), None).unwrap();
+    /// assert_eq!(poller.pull_events(1000).unwrap().len(), 1);
+    /// drop(reg);
+    /// assert!(poller.pull_events(0).unwrap().is_empty());
+    /// ```
+    pub fn add_fd<'a, F: AsFd + ?Sized>(
+        &mut self,
+        source: &'a F,
+        events: Events,
+        ctx: Option<T>,
+    ) -> Result<Registration<'a>, SysError> {
+        self.collect_garbage();
+        let reg = self.shared.add_fd(source, events)?;
+        self.inserted(reg.token, ctx);
+        Ok(reg)
+    }
+
+    /// 添加一个文件描述符到监视列表中，返回的 `Registration` 销毁时自动移除。
+    ///
+    /// 与 `add` 一样不会转移 `fd` 的所有权，请确保在 `Registration` 销毁前 `fd` 都是可用的。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use poller::{Events, Poller};
+    /// let mut poller = Poller::new().unwrap();
+    /// let reg = poller.register(1, Events::new().write().one_shot(), None).unwrap();
+    /// assert_eq!(poller.pull_events(1000).unwrap().len(), 1);
+    /// reg.rearm().unwrap();
+    /// assert_eq!(poller.pull_events(1000).unwrap().len(), 1);
+    /// ```
+    pub fn register(
+        &mut self,
+        fd: i32,
+        events: Events,
+        ctx: Option<T>,
+    ) -> Result<Registration<'static>, SysError> {
+        self.collect_garbage();
+        let reg = self.shared.register(fd, events)?;
+        self.inserted(reg.token, ctx);
+        Ok(reg)
+    }
+
+    /// 添加一个文件描述符来源到监视列表中，并把 `source` 的所有权转移到 `Poller` 内，
+    /// 返回标识此次注册的 `Token`。
+    ///
+    /// `source` 转换为 `OwnedFd` 后，在 `remove` 或 `Poller` 销毁时关闭，添加失败时也会立即关闭。
+    /// 相同编号的 `fd` 被重新注册时，原来的所有权被放弃而不再关闭。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use poller::{Events, Poller};
+    /// use std::os::unix::io::AsRawFd;
+    /// use std::os::unix::net::UnixStream;
+    /// let mut poller = Poller::new().unwrap();
+    /// let (a, _b) = UnixStream::pair().unwrap();
+    /// let fd = a.as_raw_fd();
+    /// poller.add_owned(a, Events::new().write(), None).unwrap();
+    /// poller.remove(fd).unwrap();
+    /// ```
+    pub fn add_owned<F: Into<OwnedFd>>(
+        &mut self,
+        source: F,
+        events: Events,
+        ctx: Option<T>,
+    ) -> Result<Token, SysError> {
+        self.collect_garbage();
+        let token = self.shared.add_owned(source, events)?;
+        self.inserted(token, ctx);
+        Ok(token)
+    }
+
+    /// 保存新添加的监视项的上下文。
+    fn inserted(&mut self, token: Token, ctx: Option<T>) {
+        self.ctxs.set(token, ctx);
+        if cfg!(debug_assertions) && self.audit_on_add {
+            if let Ok(issues) = self.audit() {
+                assert!(issues.is_empty(), "Poller audit failed: {:?}", issues);
+            }
+        }
+    }
+
+    /// 回收已在 `Poller` 以外移除的监视项及已上报的定时器的上下文。
+    fn collect_garbage(&mut self) {
+        let garbage = std::mem::take(&mut *self.shared.garbage.lock().unwrap());
+        for token in garbage {
+            self.ctxs.free(token);
+        }
+        for id in self.wheel.get_mut().unwrap().collect() {
+            self.timer_ctxs.free(id.key());
+        }
+    }
+
+    /// 添加一个在 `timeout` 后到期的软件定时器，返回标识此定时器的 `TimerId`。
+    ///
+    /// 定时器保存在 `Poller` 内的分层时间轮中，精度为毫秒，添加及取消的开销很小，
+    /// 适合为大量连接分别设置超时。到期后在 `pull_events` 等函数中上报一次，事件的文件描述符为 -1，
+    /// 事件集合为 `Events::timeout`，`Token` 与 `TimerId` 对应且不会与监视项的相同；等待时的超时会自动缩短到下一个定时器的到期时间。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use poller::{Events, Poller, Token};
+    /// use std::time::Duration;
+    /// let mut poller = Poller::<&str>::new_typed().unwrap();
+    /// let id = poller.add_timeout(Duration::from_millis(10), Some("idle"));
+    /// let events = poller.pull_events(-1).unwrap();
+    /// assert!(events[0].1.has_timeout());
+    /// assert_eq!(events[0].2, Some(&"idle"));
+    /// assert_eq!(events[0].3, Token::from(id));
+    /// ```
+    pub fn add_timeout(&mut self, timeout: Duration, ctx: Option<T>) -> TimerId {
+        self.collect_garbage();
+        let deadline = Instant::now()
+            .checked_add(timeout)
+            .unwrap_or_else(|| Instant::now() + Duration::from_secs(u32::MAX as u64));
+        let id = self.wheel.get_mut().unwrap().insert(deadline);
+        self.timer_ctxs.set(id.key(), ctx);
+        id
+    }
+
+    /// 取消一个尚未到期的软件定时器，已到期或已取消时返回 `false`。
+    pub fn cancel(&mut self, id: TimerId) -> bool {
+        self.collect_garbage();
+        let cancelled = self.wheel.get_mut().unwrap().cancel(id);
+        if cancelled {
+            self.timer_ctxs.free(id.key());
+        }
+        cancelled
+    }
+
+    fn ctx(&self, token: Token) -> Option<&T> {
+        self.ctxs.get(token)
+    }
+
+    /// 返回绑定到此 `Poller` 的唤醒器，首次调用时创建。
+    ///
+    /// 唤醒器可以克隆并发送到其他线程，用于中断正在进行的等待。
+    pub fn waker(&mut self) -> Result<Waker, SysError> {
+        if let Some(waker) = self.waker.as_ref() {
+            return Ok(waker.clone());
+        }
+        let waker = Waker::new()?;
+        self.shared
+            .backend
+            .register(waker.fd(), Events::new().read(), WAKER_DATA)?;
+        self.waker = Some(waker.clone());
+        Ok(waker)
+    }
+
+    /// 将一个文件描述符从监视列表中移除。
+    ///
+    /// 通过 `add_owned` 添加的文件描述符来源会在移除后被销毁。`fd` 已被关闭等导致后端注销失败时
+    /// 监视项及上下文仍会被移除，同时返回注销的错误。
+    pub fn remove(&mut self, fd: i32) -> Result<(), SysError> {
+        self.collect_garbage();
+        let (token, r) = self.shared.remove_fd(fd)?;
+        self.ctxs.free(token);
+        r
+    }
+
+    /// 修改一个已监视文件描述符的事件集合，保留原有的上下文。
+    ///
+    /// 修改后的监视项总是处于启用状态。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use poller::{Events, Poller};
+    /// let mut poller = Poller::new().unwrap();
+    /// poller.add(1, Events::new().write(), None).unwrap();
+    /// poller.modify(1, Events::new()).unwrap();
+    /// assert!(poller.pull_events(0).unwrap().is_empty());
+    /// ```
+    pub fn modify(&mut self, fd: i32, events: Events) -> Result<(), SysError> {
+        self.collect_garbage();
+        self.shared.modify_fd(fd, Some(events))?;
+        Ok(())
+    }
+
+    /// 修改一个已监视文件描述符的事件集合，同时替换其上下文。
+    pub fn modify_with_context(
+        &mut self,
+        fd: i32,
+        events: Events,
+        ctx: Option<T>,
+    ) -> Result<(), SysError> {
+        self.collect_garbage();
+        let token = self.shared.modify_fd(fd, Some(events))?;
+        self.ctxs.set(token, ctx);
+        Ok(())
+    }
+
+    /// 使用已保存的事件集合重新启用一个单次触发的文件描述符。
+    ///
+    /// 单次触发（`Events::one_shot`）的文件描述符在上报一次事件后即停止监视，
+    /// 在调用此函数前其他线程的 `pull_events` 不会再收到它的事件。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use poller::{Events, Poller};
+    /// let mut poller = Poller::new().unwrap();
+    /// poller.add(1, Events::new().write().one_shot(), None).unwrap();
+    /// assert_eq!(poller.pull_events(1000).unwrap().len(), 1);
+    /// assert_eq!(poller.is_armed(1), Some(false));
+    /// poller.rearm(1).unwrap();
+    /// assert_eq!(poller.is_armed(1), Some(true));
+    /// ```
+    pub fn rearm(&self, fd: i32) -> Result<(), SysError> {
+        self.shared.modify_fd(fd, None)?;
+        Ok(())
+    }
+
+    /// 检查一个文件描述符是否处于启用状态，未被监视时返回 `None`。
+    pub fn is_armed(&self, fd: i32) -> Option<bool> {
+        self.shared.slab().find(fd).map(|(_, v)| v.is_armed())
+    }
+
+    /// 返回监视的文件描述符数量，包括通过 `Registry` 添加的。
+    pub fn len(&self) -> usize {
+        self.shared.slab().fds.len()
+    }
+
+    /// 检查是否没有监视任何文件描述符。
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// 检查一个文件描述符是否在监视列表中。
+    pub fn contains(&self, fd: i32) -> bool {
+        self.shared.token(fd).is_some()
+    }
+
+    /// 返回一个文件描述符监视的事件集合，未被监视时返回 `None`。
+    pub fn interest(&self, fd: i32) -> Option<Events> {
+        self.shared.slab().find(fd).map(|(_, v)| v.events())
+    }
+
+    /// 返回一个文件描述符关联的上下文，未被监视或没有上下文时返回 `None`。
+    pub fn context(&self, fd: i32) -> Option<&T> {
+        self.shared.token(fd).and_then(|token| self.ctx(token))
+    }
+
+    /// 返回遍历所有监视项的迭代器，每项的事件集合为监视的事件集合。
+    ///
+    /// 迭代的是调用时的监视项快照，迭代期间可以添加、移除文件描述符，但不会反映到迭代结果中。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use poller::{Events, Poller};
+    /// let mut poller = Poller::new().unwrap();
+    /// poller.add(1, Events::new().write(), None).unwrap();
+    /// for (fd, events, ctx, token) in poller.iter() {
+    ///     println!("Fd={}, Events={}, Context={:?}, Token={:?}", fd, events, ctx, token);
+    /// }
+    /// ```
+    pub fn iter(&self) -> WatchIter<'_, T> {
+        let watches: Vec<_> = {
+            let slab = self.shared.slab();
+            slab.slots
+                .iter()
+                .enumerate()
+                .filter_map(|(index, slot)| {
+                    let v = slot.watch.as_ref()?;
+                    Some((v.fd, v.events(), Token::new(index, slot.generation)))
+                })
+                .collect()
+        };
+        WatchIter {
+            poller: self,
+            inner: watches.into_iter(),
+        }
+    }
+
+    /// 核对监视列表与后端在内核中的注册项，返回发现的问题，没有问题时返回空列表。
+    ///
+    /// 用于排查 `fd` 未经 `remove` 就被关闭、编号又被复用等导致上下文错乱的问题，
+    /// 目前只有 `epoll` 后端支持（读取 `/proc/self/fdinfo/<epoll_fd>`），其他后端返回 `ENOSYS`。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use poller::{AuditIssue, Events, Poller};
+    /// let mut poller = Poller::new().unwrap();
+    /// poller.add(1, Events::new().write(), None).unwrap();
+    /// if let Ok(issues) = poller.audit() {
+    ///     assert!(issues.is_empty());
+    /// }
+    /// ```
+    pub fn audit(&self) -> Result<Vec<AuditIssue>, SysError> {
+        let slab = self.shared.slab();
+        let mut entries = self.shared.backend.entries()?;
+        entries.retain(|x| x.data != WAKER_DATA);
+        let mut issues = Vec::new();
+        // 内核总是附加错误及挂起事件，没有权限时会忽略唤醒。
+        let mask = |x: Events| x.0 & !Events::new().error().hang_up().wake_up().0;
+        for (index, slot) in slab.slots.iter().enumerate() {
+            let v = match slot.watch.as_ref() {
+                Some(v) => v,
+                None => continue,
+            };
+            let token = Token::new(index, slot.generation);
+            let entry = match entries
+                .iter()
+                .position(|x| x.fd == v.fd && x.data == token.0)
+            {
+                Some(i) => entries.swap_remove(i),
+                None => {
+                    issues.push(AuditIssue::Missing { fd: v.fd, token });
+                    continue;
+                }
+            };
+            let expected = v.events();
+            // 单次触发的监视项上报事件后内核会清空事件集合。
+            if file_id(v.fd) != Some((entry.dev, entry.ino)) {
+                issues.push(AuditIssue::Stale { fd: v.fd, token });
+            } else if v.is_armed() && mask(entry.events) != mask(expected) {
+                issues.push(AuditIssue::EventsMismatch {
+                    fd: v.fd,
+                    token,
+                    expected,
+                    actual: entry.events,
+                });
+            }
+        }
+        issues.extend(entries.into_iter().map(AuditIssue::Orphaned));
+        Ok(issues)
+    }
+
+    /// 拉取所有被监测到的 I/O 事件。
+    ///
+    /// 每次最多拉取 `max_events` 个事件，没有监视任何文件描述符时仅等待 `timeout_ms` 毫秒，
+    /// `timeout_ms` 为负数时表示无限等待。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use poller::{Events, Poller};
+    /// let mut poller = Poller::new().unwrap();
+    /// poller.add(1, Events::new().write(), None).unwrap();
+    /// for (fd, events, ctx, token) in poller.pull_events(1000).unwrap().iter() {
+    ///     println!("Fd={}, Events={}, Context={:?}, Token={:?}", fd, events, ctx, token);
+    /// }
+    /// ```
+    pub fn pull_events(&self, timeout_ms: i32) -> Result<Vec<EventData<'_, T>>, SysError> {
+        self.pull_events_timeout(timeout_from_ms(timeout_ms))
+    }
+
+    /// 拉取所有被监测到的 I/O 事件，`timeout` 为 `None` 时表示无限等待。
+    ///
+    /// 后端支持时使用纳秒精度等待（如 `epoll_pwait2`），否则向上取整到后端支持的精度，
+    /// 保证等待时间不会短于 `timeout`。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use poller::Poller;
+    /// use std::time::Duration;
+    /// let poller = Poller::new().unwrap();
+    /// let events = poller.pull_events_timeout(Some(Duration::from_micros(250))).unwrap();
+    /// assert!(events.is_empty());
+    /// ```
+    pub fn pull_events_timeout(
+        &self,
+        timeout: Option<Duration>,
+    ) -> Result<Vec<EventData<'_, T>>, SysError> {
+        let mut buf = EventBuffer::with_capacity(self.max_events);
+        self.wait(&mut buf, timeout, None)?;
+        Ok(buf.iter().map(|(x, fd)| self.event_data(x, fd)).collect())
+    }
+
+    /// 拉取所有被监测到的 I/O 事件，最晚在 `deadline` 时返回。
+    pub fn pull_events_deadline(
+        &self,
+        deadline: Instant,
+    ) -> Result<Vec<EventData<'_, T>>, SysError> {
+        self.pull_events_timeout(Some(deadline.saturating_duration_since(Instant::now())))
+    }
+
+    /// 拉取所有被监测到的 I/O 事件，等待期间原子地将当前线程的信号掩码替换为 `sigmask`。
+    ///
+    /// 通常先阻塞需要处理的信号，再在等待时解除阻塞，从而避免检查信号标志与进入等待之间的竞态。
+    /// 信号中断时总是返回 `EINTR`，不受 `retry_on_interrupt` 影响。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use poller::{signal::SigSet, Poller};
+    /// use std::time::Duration;
+    /// let poller = Poller::new().unwrap();
+    /// let set = SigSet::empty().with(libc::SIGTERM);
+    /// let old = set.block().unwrap();
+    /// let events = poller.pull_events_with_sigmask(Some(Duration::from_millis(10)), &old);
+    /// assert!(events.unwrap().is_empty());
+    /// set.unblock().unwrap();
+    /// ```
+    pub fn pull_events_with_sigmask(
+        &self,
+        timeout: Option<Duration>,
+        sigmask: &SigSet,
+    ) -> Result<Vec<EventData<'_, T>>, SysError> {
+        let mut buf = EventBuffer::with_capacity(self.max_events);
+        self.wait(&mut buf, timeout, Some(sigmask))?;
+        Ok(buf.iter().map(|(x, fd)| self.event_data(x, fd)).collect())
+    }
+
+    /// 拉取被监测到的 I/O 事件到可复用的缓冲区中。
+    ///
+    /// 每次最多拉取 `buf.capacity()` 个事件，使用 `epoll` 后端时整个过程不会分配内存。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use poller::{EventBuffer, Events, Poller};
+    /// let mut poller = Poller::new().unwrap();
+    /// let mut buf = EventBuffer::with_capacity(16);
+    /// poller.add(1, Events::new().write(), None).unwrap();
+    /// for (fd, events, ctx, token) in poller.poll_into(&mut buf, 1000).unwrap() {
+    ///     println!("Fd={}, Events={}, Context={:?}, Token={:?}", fd, events, ctx, token);
+    /// }
+    /// ```
+    pub fn poll_into<'a>(
+        &'a self,
+        buf: &'a mut EventBuffer,
+        timeout_ms: i32,
+    ) -> Result<EventIter<'a, T>, SysError> {
+        self.poll_into_timeout(buf, timeout_from_ms(timeout_ms))
+    }
+
+    /// 拉取被监测到的 I/O 事件到可复用的缓冲区中，`timeout` 为 `None` 时表示无限等待。
+    pub fn poll_into_timeout<'a>(
+        &'a self,
+        buf: &'a mut EventBuffer,
+        timeout: Option<Duration>,
+    ) -> Result<EventIter<'a, T>, SysError> {
+        self.wait(buf, timeout, None)?;
+        Ok(EventIter {
+            poller: self,
+            inner: buf.iter(),
+        })
+    }
+
+    /// 拉取被监测到的 I/O 事件并逐个交给 `f` 处理，返回处理的事件数量。
+    ///
+    /// 与 `pull_events` 不同的是 `f` 可以通过 `&mut T` 修改事件对应的上下文。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use poller::{Events, Poller};
+    /// let mut poller = Poller::<u32>::new_typed().unwrap();
+    /// poller.add(1, Events::new().write(), Some(0)).unwrap();
+    /// poller.dispatch(1000, |_fd, _events, ctx, _token| *ctx.unwrap() += 1).unwrap();
+    /// assert_eq!(poller.pull_events(1000).unwrap()[0].2, Some(&1));
+    /// ```
+    pub fn dispatch<F>(&mut self, timeout_ms: i32, f: F) -> Result<usize, SysError>
+    where
+        F: FnMut(i32, Events, Option<&mut T>, Token),
+    {
+        self.dispatch_timeout(timeout_from_ms(timeout_ms), f)
+    }
+
+    /// 拉取被监测到的 I/O 事件并逐个交给 `f` 处理，`timeout` 为 `None` 时表示无限等待。
+    pub fn dispatch_timeout<F>(
+        &mut self,
+        timeout: Option<Duration>,
+        mut f: F,
+    ) -> Result<usize, SysError>
+    where
+        F: FnMut(i32, Events, Option<&mut T>, Token),
+    {
+        self.collect_garbage();
+        let mut buf = EventBuffer::with_capacity(self.max_events);
+        self.wait(&mut buf, timeout, None)?;
+        for (x, fd) in buf.iter() {
+            let token = Token(x.0);
+            let ctx = if x.1.has_timeout() {
+                self.timer_ctxs.get_mut(TimerId::from_token(token).key())
+            } else {
+                self.ctxs.get_mut(token)
+            };
+            f(*fd, x.1, ctx, token);
+        }
+        Ok(buf.len)
+    }
+
+    fn wait(
+        &self,
+        buf: &mut EventBuffer,
+        timeout: Option<Duration>,
+        sigmask: Option<&SigSet>,
+    ) -> Result<(), SysError> {
+        buf.len = 0;
+        let backend = &self.shared.backend;
+        let deadline = timeout.and_then(|d| Instant::now().checked_add(d));
+        let mut timeout = timeout;
+        loop {
+            // 等待时间不超过下一个定时器的到期时间。
+            let next = self.wheel.lock().unwrap().next_timeout(Instant::now());
+            let clamped = next.is_some_and(|x| timeout.is_none_or(|t| x < t));
+            let wait_timeout = if clamped { next } else { timeout };
+            let r = match sigmask {
+                Some(sigmask) => backend.wait_with_sigmask(&mut buf.events, wait_timeout, sigmask),
+                None => backend.wait(&mut buf.events, wait_timeout),
+            };
+            let (len, interrupted) = match r {
+                Err(e)
+                    if self.retry_on_interrupt
+                        && sigmask.is_none()
+                        && i32::from(e) == libc::EINTR =>
+                {
+                    (0, true)
+                }
+                r => (r?, false),
+            };
+            // 丢弃已被注销或 `Token` 已失效的监视项的事件，其余的依次前移。
+            let mut woken = false;
+            let mut n = 0;
+            {
+                let slab = self.shared.slab();
+                for i in 0..len {
+                    let x = buf.events[i];
+                    if x.0 == WAKER_DATA {
+                        if let Some(waker) = self.waker.as_ref() {
+                            waker.drain();
+                        }
+                        woken = true;
+                        continue;
+                    }
+                    if let Some(v) = slab.get(Token(x.0)) {
+                        if v.events().has_one_shot() {
+                            v.armed.store(false, Ordering::Release);
+                        }
+                        buf.events[n] = x;
+                        buf.fds[n] = v.fd;
+                        n += 1;
+                    }
+                }
+            }
+            // 到期的定时器追加在 I/O 事件之后，放不下的留到下次上报。
+            let now = Instant::now();
+            let mut wheel = self.wheel.lock().unwrap();
+            while n < buf.capacity() {
+                match wheel.pop(now) {
+                    Some(id) => {
+                        buf.events[n] = (Token::from(id).0, Events::new().timeout());
+                        buf.fds[n] = -1;
+                        n += 1;
+                    }
+                    None => break,
+                }
+            }
+            buf.len = n;
+            // 因定时器缩短的等待或被信号中断的等待在没有事件时继续，直到调用者指定的超时。
+            if n > 0 || woken || !(clamped || interrupted) {
+                return Ok(());
+            }
+            if let Some(deadline) = deadline {
+                if now >= deadline {
+                    return Ok(());
+                }
+                timeout = Some(deadline - now);
+            }
+        }
+    }
+
+    fn event_data(&self, x: &RawEvent, fd: &i32) -> EventData<'_, T> {
+        let token = Token(x.0);
+        let ctx = if x.1.has_timeout() {
+            self.timer_ctxs.get(TimerId::from_token(token).key())
+        } else {
+            self.ctxs.get(token)
+        };
+        (*fd, x.1, ctx, token)
+    }
+}
+
+/// 返回文件描述符当前指向的文件所在的设备编号及 inode 编号。
+fn file_id(fd: i32) -> Option<(u64, u64)> {
+    let mut st: libc::stat = unsafe { std::mem::zeroed() };
+    if unsafe { libc::fstat(fd, &mut st) } < 0 {
+        None
+    } else {
+        Some((st.st_dev as u64, st.st_ino as u64))
+    }
+}
+
+/// 定义 `Poller::audit` 发现的问题。
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum AuditIssue {
+    /// 监视项在内核中不存在，通常是 `fd` 未经 `remove` 就被关闭了。
+    Missing { fd: i32, token: Token },
+    /// `fd` 已被关闭或当前指向的文件与内核中注册的不同，通常是编号被复用了。
+    Stale { fd: i32, token: Token },
+    /// 内核中的事件集合与监视项的不一致。
+    EventsMismatch {
+        fd: i32,
+        token: Token,
+        expected: Events,
+        actual: Events,
+    },
+    /// 内核中存在但没有对应监视项的注册。
+    Orphaned(KernelEntry),
+}
+
+/// 定义可在多个线程间共享的注册表。
+///
+/// 由 `Poller::registry` 返回，可以克隆并发送到其他线程，在其他线程阻塞于 `pull_events`
+/// 时添加、移除文件描述符。通过 `Registry` 添加的监视项没有上下文，可以用返回的 `Token`
+/// 与事件对应；`Poller` 销毁后添加会返回 `EBADF`。
+///
+/// # Examples
+///
+/// ```
+/// use poller::{Events, Poller};
+/// use std::sync::Arc;
+/// let poller = Arc::new(Poller::new().unwrap());
+/// let registry = poller.registry();
+/// let t = {
+///     let poller = Arc::clone(&poller);
+///     std::thread::spawn(move || poller.pull_events(-1).unwrap()[0].3)
+/// };
+/// let token = registry.add(1, Events::new().write()).unwrap();
+/// assert_eq!(t.join().unwrap(), token);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Registry {
+    shared: Arc<Shared>,
+}
+
+impl Registry {
+    /// 添加一个文件描述符到监视列表中，同 `Poller::add`。
+    pub fn add(&self, fd: i32, events: Events) -> Result<Token, SysError> {
+        self.shared.add(Watch::new(fd, events, None))
+    }
+
+    /// 添加一个文件描述符来源到监视列表中，同 `Poller::add_fd`。
+    pub fn add_fd<'a, F: AsFd + ?Sized>(
+        &self,
+        source: &'a F,
+        events: Events,
+    ) -> Result<Registration<'a>, SysError> {
+        self.shared.add_fd(source, events)
+    }
+
+    /// 添加一个文件描述符到监视列表中，同 `Poller::register`。
+    pub fn register(&self, fd: i32, events: Events) -> Result<Registration<'static>, SysError> {
+        self.shared.register(fd, events)
+    }
+
+    /// 添加一个文件描述符来源到监视列表中并转移其所有权，同 `Poller::add_owned`。
+    pub fn add_owned<F: Into<OwnedFd>>(
+        &self,
+        source: F,
+        events: Events,
+    ) -> Result<Token, SysError> {
+        self.shared.add_owned(source, events)
+    }
+
+    /// 将一个文件描述符从监视列表中移除，同 `Poller::remove`。
+    pub fn remove(&self, fd: i32) -> Result<(), SysError> {
+        let (token, r) = self.shared.remove_fd(fd)?;
+        self.shared.garbage.lock().unwrap().push(token);
+        r
+    }
+
+    /// 修改一个已监视文件描述符的事件集合，同 `Poller::modify`。
+    pub fn modify(&self, fd: i32, events: Events) -> Result<(), SysError> {
+        self.shared.modify_fd(fd, Some(events))?;
+        Ok(())
+    }
+
+    /// 重新启用一个单次触发的文件描述符，同 `Poller::rearm`。
+    pub fn rearm(&self, fd: i32) -> Result<(), SysError> {
+        self.shared.modify_fd(fd, None)?;
+        Ok(())
+    }
+}
+
+/// 定义文件描述符注册句柄。
+///
+/// 由 `Poller::add_fd` 或 `Poller::register` 返回，销毁时自动将文件描述符从监视列表中移除，
+/// 避免提前返回等情况下遗漏注销，导致之后复用相同编号的 `fd` 收到不属于它的事件。
+#[derive(Debug)]
+pub struct Registration<'a> {
+    fd: i32,
+    token: Token,
+    shared: Arc<Shared>,
+    _source: PhantomData<&'a ()>,
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        let _ = self.release();
+    }
+}
+
+impl Registration<'_> {
+    fn new(shared: &Arc<Shared>, fd: i32, token: Token) -> Self {
+        Self {
+            fd,
+            token,
+            shared: Arc::clone(shared),
+            _source: PhantomData,
+        }
+    }
+
+    /// 返回注册的文件描述符。
+    pub fn fd(&self) -> i32 {
+        self.fd
+    }
+
+    /// 返回标识此次注册的 `Token`，与事件数据中的 `Token` 对应。
+    pub fn token(&self) -> Token {
+        self.token
+    }
+
+    /// 返回当前监视的事件集合，已被移除时返回空集合。
+    pub fn events(&self) -> Events {
+        let slab = self.shared.slab();
+        slab.get(self.token).map_or(Events::new(), |v| v.events())
+    }
+
+    /// 修改监视的事件集合，同 `Poller::modify`。
+    pub fn modify(&self, events: Events) -> Result<(), SysError> {
+        self.shared.modify(self.token, Some(events))
+    }
+
+    /// 使用已保存的事件集合重新启用单次触发的文件描述符，同 `Poller::rearm`。
+    pub fn rearm(&self) -> Result<(), SysError> {
+        self.shared.modify(self.token, None)
+    }
+
+    /// 检查是否处于启用状态。
+    pub fn is_armed(&self) -> bool {
+        let slab = self.shared.slab();
+        slab.get(self.token).is_some_and(|v| v.is_armed())
+    }
+
+    /// 将文件描述符从监视列表中移除，与直接销毁不同的是会返回移除结果。
+    pub fn deregister(self) -> Result<(), SysError> {
+        self.release()
+    }
+
+    fn release(&self) -> Result<(), SysError> {
+        // 已经通过 `Poller::remove` 移除时 `Token` 已失效，不再重复操作。
+        match self.shared.remove(self.token) {
+            Err(e) if i32::from(e) == libc::ENOENT => Ok(()),
+            r => {
+                self.shared.garbage.lock().unwrap().push(self.token);
+                r
+            }
+        }
+    }
+}
+
+/// 定义可复用的事件缓冲区。
+///
+/// 配合 `Poller::poll_into` 使用，避免每次拉取事件时分配内存。
+#[derive(Clone, Debug)]
+pub struct EventBuffer {
+    events: Vec<RawEvent>,
+    /// 与 `events` 一一对应的文件描述符。
+    fds: Vec<i32>,
+    len: usize,
+}
+
+impl EventBuffer {
+    /// 创建一个最多可容纳 `capacity` 个事件的缓冲区，`capacity` 至少为 1。
+    pub fn with_capacity(capacity: usize) -> Self {
+        Self {
+            events: vec![(0, Events::new()); capacity.max(1)],
+            fds: vec![-1; capacity.max(1)],
+            len: 0,
+        }
+    }
+
+    /// 返回缓冲区最多可容纳的事件数量。
+    pub fn capacity(&self) -> usize {
+        self.events.len()
+    }
+
+    /// 返回缓冲区中的事件数量。
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// 检查缓冲区是否为空。
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// 清空缓冲区中的事件。
+    pub fn clear(&mut self) {
+        self.len = 0;
+    }
+
+    fn iter(&self) -> BufferIter<'_> {
+        self.events[..self.len]
+            .iter()
+            .zip(self.fds[..self.len].iter())
+    }
+}
+
+/// 定义缓冲区中事件及对应文件描述符的迭代器。
+type BufferIter<'a> = std::iter::Zip<std::slice::Iter<'a, RawEvent>, std::slice::Iter<'a, i32>>;
+
+/// 定义缓冲区事件迭代器。
+#[derive(Debug)]
+pub struct EventIter<'a, T = EventContext> {
+    poller: &'a Poller<T>,
+    inner: BufferIter<'a>,
+}
+
+impl<'a, T> Iterator for EventIter<'a, T> {
+    type Item = EventData<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let poller = self.poller;
+        self.inner.next().map(|(x, fd)| poller.event_data(x, fd))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<T> ExactSizeIterator for EventIter<'_, T> {}
+
+/// 定义监视项迭代器。
+#[derive(Debug)]
+pub struct WatchIter<'a, T = EventContext> {
+    poller: &'a Poller<T>,
+    inner: std::vec::IntoIter<(i32, Events, Token)>,
+}
+
+impl<'a, T> Iterator for WatchIter<'a, T> {
+    type Item = EventData<'a, T>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let poller = self.poller;
+        self.inner
+            .next()
+            .map(|(fd, events, token)| (fd, events, poller.ctx(token), token))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.inner.size_hint()
+    }
+}
+
+impl<'a, T> IntoIterator for &'a Poller<T> {
+    type Item = EventData<'a, T>;
+    type IntoIter = WatchIter<'a, T>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    #[cfg(target_os = "linux")]
+    use crate::epoll;
+    use crate::{poll, select};
+
+    fn pipe() -> (i32, i32) {
+        let mut fds = [0i32; 2];
+        assert_eq!(unsafe { libc::pipe(fds.as_mut_ptr()) }, 0);
+        (fds[0], fds[1])
+    }
+
+    fn close(fds: &[i32]) {
+        for fd in fds {
+            unsafe { libc::close(*fd) };
+        }
+    }
+
+    #[test]
+    fn test_poller_pipe() {
+        let (rfd, wfd) = pipe();
+        let mut poller = Poller::new().unwrap();
+        let ctx: EventContext = Arc::new(rfd);
+        poller.add(rfd, Events::new().read(), Some(ctx)).unwrap();
+        assert!(poller.pull_events(0).unwrap().is_empty());
+        assert_eq!(unsafe { libc::write(wfd, b"x".as_ptr() as *const _, 1) }, 1);
+        let events = poller.pull_events(1000).unwrap();
+        assert_eq!(events.len(), 1);
+        let (fd, events, ctx, _) = events[0];
+        assert_eq!(fd, rfd);
+        assert!(events.has_read());
+        assert_eq!(ctx.unwrap().downcast_ref::<i32>(), Some(&rfd));
+        poller.remove(rfd).unwrap();
+        close(&[rfd, wfd]);
+    }
+
+    fn backends() -> Vec<Box<dyn Backend>> {
+        vec![
+            #[cfg(target_os = "linux")]
+            Box::new(epoll::Epoll::new().unwrap()),
+            Box::new(poll::Poll::new()),
+            Box::new(select::Select::new()),
+        ]
+    }
+
+    #[test]
+    fn test_poller_backends() {
+        for backend in backends() {
+            let (rfd, wfd) = pipe();
+            let mut poller = Poller::with_backend(backend);
+            poller.add(rfd, Events::new().read(), None).unwrap();
+            poller.add(wfd, Events::new().write(), None).unwrap();
+            let events = poller.pull_events(1000).unwrap();
+            assert_eq!(events.len(), 1, "{:?}", poller);
+            assert_eq!(events[0].0, wfd);
+            assert!(events[0].1.has_write());
+            assert_eq!(unsafe { libc::write(wfd, b"x".as_ptr() as *const _, 1) }, 1);
+            assert_eq!(poller.pull_events(1000).unwrap().len(), 2);
+            poller.remove(wfd).unwrap();
+            let events = poller.pull_events(1000).unwrap();
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].0, rfd);
+            assert!(events[0].1.has_read());
+            poller.remove(rfd).unwrap();
+            close(&[rfd, wfd]);
+        }
+    }
+
+    #[test]
+    fn test_poller_modify() {
+        for backend in backends() {
+            let (rfd, wfd) = pipe();
+            let mut poller = Poller::with_backend(backend);
+            poller.add(wfd, Events::new(), None).unwrap();
+            assert!(poller.pull_events(0).unwrap().is_empty());
+            poller.modify(wfd, Events::new().write()).unwrap();
+            assert!(poller.pull_events(1000).unwrap()[0].1.has_write());
+            let ctx: EventContext = Arc::new(wfd);
+            poller
+                .modify_with_context(wfd, Events::new().write(), Some(ctx))
+                .unwrap();
+            let events = poller.pull_events(1000).unwrap();
+            assert_eq!(events[0].2.unwrap().downcast_ref::<i32>(), Some(&wfd));
+            poller.modify(wfd, Events::new()).unwrap();
+            assert!(poller.pull_events(0).unwrap().is_empty());
+            assert_eq!(
+                poller.modify(rfd, Events::new().read()),
+                Err(SysError::from(libc::ENOENT))
+            );
+            poller.remove(wfd).unwrap();
+            close(&[rfd, wfd]);
+        }
+    }
+
+    #[test]
+    fn test_poller_one_shot() {
+        for backend in backends() {
+            let (rfd, wfd) = pipe();
+            let mut poller = Poller::with_backend(backend);
+            poller
+                .add(wfd, Events::new().write().one_shot(), None)
+                .unwrap();
+            assert_eq!(poller.is_armed(wfd), Some(true));
+            assert_eq!(poller.pull_events(1000).unwrap().len(), 1);
+            assert_eq!(poller.is_armed(wfd), Some(false));
+            assert!(poller.pull_events(0).unwrap().is_empty());
+            poller.rearm(wfd).unwrap();
+            assert_eq!(poller.is_armed(wfd), Some(true));
+            assert_eq!(poller.pull_events(1000).unwrap().len(), 1);
+            assert_eq!(poller.rearm(rfd), Err(SysError::from(libc::ENOENT)));
+            assert_eq!(poller.is_armed(rfd), None);
+            poller.remove(wfd).unwrap();
+            close(&[rfd, wfd]);
+        }
+    }
+
+    #[test]
+    fn test_poller_one_shot_threads() {
+        let (rfd, wfd) = pipe();
+        let mut poller = Poller::new().unwrap();
+        poller
+            .add(wfd, Events::new().write().one_shot(), None)
+            .unwrap();
+        let poller = Arc::new(poller);
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let poller = Arc::clone(&poller);
+                std::thread::spawn(move || poller.pull_events(200).unwrap().len())
+            })
+            .collect();
+        let total: usize = workers.into_iter().map(|x| x.join().unwrap()).sum();
+        assert_eq!(total, 1);
+        close(&[rfd, wfd]);
+    }
+
+    #[test]
+    fn test_poller_poll_into() {
+        for backend in backends() {
+            let (rfd, wfd) = pipe();
+            let mut poller = Poller::with_backend(backend);
+            let mut buf = EventBuffer::with_capacity(1);
+            poller.add(rfd, Events::new().read(), None).unwrap();
+            assert_eq!(poller.poll_into(&mut buf, 0).unwrap().len(), 0);
+            assert!(buf.is_empty());
+            let ctx: EventContext = Arc::new(wfd);
+            poller.add(wfd, Events::new().write(), Some(ctx)).unwrap();
+            assert_eq!(unsafe { libc::write(wfd, b"x".as_ptr() as *const _, 1) }, 1);
+            for _ in 0..100 {
+                let events: Vec<_> = poller.poll_into(&mut buf, 1000).unwrap().collect();
+                assert_eq!(events.len(), 1);
+                if events[0].0 == wfd {
+                    assert_eq!(events[0].2.unwrap().downcast_ref::<i32>(), Some(&wfd));
+                }
+                assert_eq!(buf.len(), 1);
+            }
+            buf.clear();
+            assert!(buf.is_empty());
+            assert_eq!(buf.capacity(), 1);
+            poller.remove(rfd).unwrap();
+            poller.remove(wfd).unwrap();
+            close(&[rfd, wfd]);
+        }
+    }
+
+    #[test]
+    fn test_poller_empty() {
+        for backend in backends() {
+            let poller = Poller::with_backend(backend);
+            let now = std::time::Instant::now();
+            assert!(poller.pull_events(50).unwrap().is_empty());
+            assert!(now.elapsed() >= std::time::Duration::from_millis(40));
+        }
+    }
+
+    #[test]
+    fn test_poller_max_events() {
+        for backend in backends() {
+            let (rfd, wfd) = pipe();
+            let mut poller = Poller::with_backend(backend).max_events(1);
+            poller.add(rfd, Events::new().read(), None).unwrap();
+            poller.add(wfd, Events::new().write(), None).unwrap();
+            assert_eq!(unsafe { libc::write(wfd, b"x".as_ptr() as *const _, 1) }, 1);
+            assert_eq!(poller.pull_events(1000).unwrap().len(), 1);
+            let mut poller = poller.max_events(0);
+            assert_eq!(poller.pull_events(1000).unwrap().len(), 1);
+            poller = poller.max_events(16);
+            assert_eq!(poller.pull_events(1000).unwrap().len(), 2);
+            close(&[rfd, wfd]);
+        }
+    }
+
+    #[test]
+    fn test_poller_timeout() {
+        for backend in backends() {
+            let poller = Poller::with_backend(backend);
+            let now = std::time::Instant::now();
+            let timeout = Duration::from_micros(250);
+            assert!(poller
+                .pull_events_timeout(Some(timeout))
+                .unwrap()
+                .is_empty());
+            assert!(now.elapsed() >= timeout);
+            let deadline = std::time::Instant::now() + Duration::from_millis(20);
+            assert!(poller.pull_events_deadline(deadline).unwrap().is_empty());
+            assert!(std::time::Instant::now() >= deadline);
+        }
+    }
+
+    extern "C" fn on_signal(_: libc::c_int) {}
+
+    /// 在另一个线程中等待 200 毫秒，并在等待期间向其发送信号。
+    fn pull_interrupted(poller: Poller) -> (Result<usize, SysError>, Duration) {
+        unsafe {
+            let mut sa: libc::sigaction = std::mem::zeroed();
+            sa.sa_sigaction = on_signal as *const () as libc::sighandler_t;
+            libc::sigaction(libc::SIGUSR1, &sa, std::ptr::null_mut());
+        }
+        let (tx, rx) = std::sync::mpsc::channel();
+        let waiter = std::thread::spawn(move || {
+            tx.send(unsafe { libc::pthread_self() }).unwrap();
+            let now = std::time::Instant::now();
+            let r = poller.pull_events(200).map(|x| x.len());
+            (r, now.elapsed())
+        });
+        let tid = rx.recv().unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        unsafe { libc::pthread_kill(tid, libc::SIGUSR1) };
+        waiter.join().unwrap()
+    }
+
+    #[test]
+    fn test_poller_interrupted() {
+        let (r, _) = pull_interrupted(Poller::new().unwrap());
+        assert_eq!(r, Err(SysError::from(libc::EINTR)));
+        let (r, elapsed) = pull_interrupted(Poller::new().unwrap().retry_on_interrupt(true));
+        assert_eq!(r, Ok(0));
+        assert!(elapsed >= Duration::from_millis(200));
+        assert!(elapsed < Duration::from_millis(400));
+    }
+
+    static SIGNALED: std::sync::atomic::AtomicBool = std::sync::atomic::AtomicBool::new(false);
+
+    extern "C" fn on_sigusr2(_: libc::c_int) {
+        SIGNALED.store(true, std::sync::atomic::Ordering::SeqCst);
+    }
+
+    #[test]
+    fn test_poller_sigmask() {
+        unsafe {
+            let mut sa: libc::sigaction = std::mem::zeroed();
+            sa.sa_sigaction = on_sigusr2 as *const () as libc::sighandler_t;
+            libc::sigaction(libc::SIGUSR2, &sa, std::ptr::null_mut());
+        }
+        std::thread::spawn(|| {
+            let set = SigSet::empty().with(libc::SIGUSR2);
+            let old = set.block().unwrap();
+            for backend in backends() {
+                let poller = Poller::with_backend(backend);
+                // 信号在阻塞期间到达，只会在等待时被处理。
+                SIGNALED.store(false, std::sync::atomic::Ordering::SeqCst);
+                unsafe { libc::pthread_kill(libc::pthread_self(), libc::SIGUSR2) };
+                assert!(!SIGNALED.load(std::sync::atomic::Ordering::SeqCst));
+                let r = poller.pull_events_with_sigmask(Some(Duration::from_secs(5)), &old);
+                assert_eq!(r.unwrap_err(), SysError::from(libc::EINTR));
+                assert!(SIGNALED.load(std::sync::atomic::Ordering::SeqCst));
+                let r = poller.pull_events_with_sigmask(Some(Duration::from_millis(1)), &old);
+                assert!(r.unwrap().is_empty());
+            }
+            set.unblock().unwrap();
+        })
+        .join()
+        .unwrap();
+    }
+
+    fn is_open(fd: i32) -> bool {
+        unsafe { libc::fcntl(fd, libc::F_GETFD) >= 0 }
+    }
+
+    #[test]
+    fn test_poller_add_fd() {
+        use std::os::unix::io::AsRawFd;
+        use std::os::unix::net::UnixStream;
+        for backend in backends() {
+            let (a, _b) = UnixStream::pair().unwrap();
+            let mut poller = Poller::with_backend(backend);
+            let reg = poller.add_fd(&a, Events::new().write(), None).unwrap();
+            assert_eq!(reg.fd(), a.as_raw_fd());
+            assert_eq!(poller.pull_events(1000).unwrap().len(), 1);
+            drop(reg);
+            assert!(poller.pull_events(0).unwrap().is_empty());
+            assert_eq!(poller.is_armed(a.as_raw_fd()), None);
+            assert_eq!(
+                poller.rearm(a.as_raw_fd()),
+                Err(SysError::from(libc::ENOENT))
+            );
+            // 注销后可以重新添加，且旧句柄不会影响新的监视项。
+            let reg = poller.add_fd(&a, Events::new().write(), None).unwrap();
+            poller.remove(a.as_raw_fd()).unwrap();
+            poller
+                .add(a.as_raw_fd(), Events::new().write(), None)
+                .unwrap();
+            reg.deregister().unwrap();
+            assert_eq!(poller.pull_events(1000).unwrap().len(), 1);
+            poller.remove(a.as_raw_fd()).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_poller_register() {
+        for backend in backends() {
+            let (rfd, wfd) = pipe();
+            let mut poller = Poller::with_backend(backend);
+            {
+                let reg = poller.register(wfd, Events::new().read(), None).unwrap();
+                assert!(poller.pull_events(0).unwrap().is_empty());
+                reg.modify(Events::new().write().one_shot()).unwrap();
+                assert_eq!(reg.events(), Events::new().write().one_shot());
+                assert_eq!(poller.pull_events(1000).unwrap().len(), 1);
+                assert!(!reg.is_armed());
+                assert!(poller.pull_events(0).unwrap().is_empty());
+                reg.rearm().unwrap();
+                assert_eq!(poller.is_armed(wfd), Some(true));
+                assert_eq!(poller.pull_events(1000).unwrap().len(), 1);
+            }
+            assert_eq!(poller.is_armed(wfd), None);
+            assert_eq!(
+                poller.modify(wfd, Events::new().write()),
+                Err(SysError::from(libc::ENOENT))
+            );
+            // 提前移除后句柄的操作失败，销毁时也不会影响复用相同编号的监视项。
+            let reg = poller.register(wfd, Events::new().write(), None).unwrap();
+            poller.remove(wfd).unwrap();
+            assert_eq!(reg.rearm(), Err(SysError::from(libc::ENOENT)));
+            poller.add(wfd, Events::new().write(), None).unwrap();
+            drop(reg);
+            assert_eq!(poller.pull_events(1000).unwrap().len(), 1);
+            close(&[rfd, wfd]);
+        }
+    }
+
+    #[test]
+    fn test_poller_tokens() {
+        for backend in backends() {
+            let (rfd, wfd) = pipe();
+            let dfd = unsafe { libc::dup(wfd) };
+            let mut poller = Poller::with_backend(backend);
+            let t1 = poller.add(wfd, Events::new().write(), None).unwrap();
+            let t2 = poller.add(dfd, Events::new().write(), None).unwrap();
+            assert_ne!(t1, t2);
+            let mut events = poller.pull_events(1000).unwrap();
+            events.sort_by_key(|x| x.3);
+            assert_eq!((events[0].0, events[0].3), (wfd, t1));
+            assert_eq!((events[1].0, events[1].3), (dfd, t2));
+            // 槽位复用后旧的令牌失效。
+            poller.remove(wfd).unwrap();
+            let t3 = poller.add(wfd, Events::new().write(), None).unwrap();
+            assert_ne!(t1, t3);
+            assert_eq!(t1.index(), t3.index());
+            close(&[rfd, wfd, dfd]);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_poller_reused_fd() {
+        let mut poller = Poller::with_backend(Box::new(epoll::Epoll::new().unwrap()));
+        let (rfd, wfd) = pipe();
+        let (rfd2, wfd2) = pipe();
+        let reg = poller.register(wfd, Events::new().write(), None).unwrap();
+        // 原来的描述被关闭，相同编号的 fd 作为新的监视项注册，旧句柄销毁时不影响新的监视项。
+        assert_eq!(unsafe { libc::dup2(wfd2, wfd) }, wfd);
+        let token = poller.add(wfd, Events::new().write(), None).unwrap();
+        assert_ne!(reg.token(), token);
+        drop(reg);
+        let events = poller.pull_events(1000).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].3, token);
+        close(&[rfd, wfd, rfd2, wfd2]);
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_poller_reused_owned_fd() {
+        use std::os::unix::io::{FromRawFd, OwnedFd};
+        let mut poller = Poller::with_backend(Box::new(epoll::Epoll::new().unwrap()));
+        let (rfd, wfd) = pipe();
+        let (rfd2, wfd2) = pipe();
+        let w = unsafe { OwnedFd::from_raw_fd(wfd) };
+        poller.add_owned(w, Events::new().write(), None).unwrap();
+        // 被替换的监视项的所有者不能关闭已属于新文件的编号。
+        assert_eq!(unsafe { libc::dup2(wfd2, wfd) }, wfd);
+        poller.add(wfd, Events::new().write(), None).unwrap();
+        assert!(is_open(wfd));
+        drop(poller);
+        assert!(is_open(wfd));
+        close(&[rfd, wfd, rfd2, wfd2]);
+    }
+
+    #[test]
+    fn test_poller_typed() {
+        for backend in backends() {
+            let (rfd, wfd) = pipe();
+            let mut poller = Poller::<Vec<i32>>::with_backend_typed(backend);
+            poller.add(rfd, Events::new().read(), None).unwrap();
+            poller
+                .add(wfd, Events::new().write(), Some(vec![]))
+                .unwrap();
+            for _ in 0..2 {
+                let n = poller
+                    .dispatch(1000, |fd, _, ctx, _| ctx.unwrap().push(fd))
+                    .unwrap();
+                assert_eq!(n, 1);
+            }
+            let events = poller.pull_events(1000).unwrap();
+            assert_eq!(events[0].2, Some(&vec![wfd, wfd]));
+            close(&[rfd, wfd]);
+        }
+    }
+
+    #[cfg(target_os = "linux")]
+    #[test]
+    fn test_poller_audit() {
+        use std::os::unix::io::AsRawFd;
+        let backend = epoll::Epoll::new().unwrap();
+        let epfd = backend.as_raw_fd();
+        let mut poller = Poller::with_backend(Box::new(backend));
+        let (rfd, wfd) = pipe();
+        let (rfd2, wfd2) = pipe();
+        let t1 = poller.add(rfd, Events::new().read(), None).unwrap();
+        let t2 = poller
+            .add(wfd, Events::new().write().one_shot(), None)
+            .unwrap();
+        assert_eq!(poller.audit().unwrap(), vec![]);
+        // 单次触发的监视项上报后仍然是一致的。
+        assert_eq!(poller.pull_events(1000).unwrap().len(), 1);
+        assert_eq!(poller.audit().unwrap(), vec![]);
+        // 绕过 Poller 直接修改或添加的注册项。
+        let mut ev = libc::epoll_event {
+            events: libc::EPOLLOUT as u32,
+            u64: u64::from(t1),
+        };
+        unsafe { libc::epoll_ctl(epfd, libc::EPOLL_CTL_MOD, rfd, &mut ev) };
+        ev.u64 = 1234;
+        unsafe { libc::epoll_ctl(epfd, libc::EPOLL_CTL_ADD, rfd2, &mut ev) };
+        let issues = poller.audit().unwrap();
+        assert_eq!(issues.len(), 2);
+        assert_eq!(
+            issues[0],
+            AuditIssue::EventsMismatch {
+                fd: rfd,
+                token: t1,
+                expected: Events::new().read(),
+                actual: Events::new().write().error().hang_up(),
+            }
+        );
+        assert!(matches!(issues[1], AuditIssue::Orphaned(x) if x.fd == rfd2 && x.data == 1234));
+        unsafe { libc::epoll_ctl(epfd, libc::EPOLL_CTL_DEL, rfd2, std::ptr::null_mut()) };
+        poller.modify(rfd, Events::new().read()).unwrap();
+        // 未经 remove 就关闭或复用了编号。
+        let dfd = unsafe { libc::dup(wfd) };
+        assert_eq!(unsafe { libc::dup2(wfd2, wfd) }, wfd);
+        close(&[rfd]);
+        assert_eq!(
+            poller.audit().unwrap(),
+            vec![
+                AuditIssue::Missing { fd: rfd, token: t1 },
+                AuditIssue::Stale { fd: wfd, token: t2 },
+            ]
+        );
+        close(&[wfd, dfd, rfd2, wfd2]);
+    }
+
+    #[cfg(all(target_os = "linux", debug_assertions))]
+    #[test]
+    #[should_panic(expected = "Poller audit failed")]
+    fn test_poller_audit_on_add() {
+        let backend = Box::new(epoll::Epoll::new().unwrap());
+        let mut poller = Poller::with_backend(backend).audit_on_add(true);
+        let (rfd, wfd) = pipe();
+        let (rfd2, wfd2) = pipe();
+        poller.add(rfd, Events::new().read(), None).unwrap();
+        close(&[rfd, wfd]);
+        let _ = poller.add(wfd2, Events::new().write(), None);
+        close(&[rfd2, wfd2]);
+    }
+
+    #[test]
+    fn test_poller_introspection() {
+        let (rfd, wfd) = pipe();
+        let mut poller = Poller::<&str>::new_typed().unwrap();
+        assert!(poller.is_empty());
+        let t1 = poller.add(rfd, Events::new().read(), Some("r")).unwrap();
+        let reg = poller.register(wfd, Events::new().write(), None).unwrap();
+        assert_eq!(poller.len(), 2);
+        assert!(poller.contains(rfd) && poller.contains(wfd));
+        assert_eq!(poller.interest(rfd), Some(Events::new().read()));
+        assert_eq!(poller.context(rfd), Some(&"r"));
+        assert!(poller.context(wfd).is_none());
+        let mut watches: Vec<_> = poller.iter().collect();
+        watches.sort_by_key(|x| x.0);
+        assert_eq!(watches[0], (rfd, Events::new().read(), Some(&"r"), t1));
+        assert_eq!(watches[1], (wfd, Events::new().write(), None, reg.token()));
+        // 迭代期间移除监视项不会死锁。
+        let mut reg = Some(reg);
+        assert_eq!(poller.iter().inspect(|_| drop(reg.take())).count(), 2);
+        assert_eq!(poller.len(), 1);
+        assert!(!poller.contains(wfd));
+        assert_eq!(poller.interest(wfd), None);
+        poller.remove(rfd).unwrap();
+        assert!(poller.is_empty());
+        assert_eq!((&poller).into_iter().count(), 0);
+        close(&[rfd, wfd]);
+    }
+
+    #[test]
+    fn test_poller_waker() {
+        for backend in backends() {
+            let (rfd, wfd) = pipe();
+            let mut poller = Poller::with_backend(backend);
+            poller.add(rfd, Events::new().read(), None).unwrap();
+            let waker = poller.waker().unwrap();
+            // 唤醒不会被上报，且读空后不会重复唤醒。
+            waker.wake().unwrap();
+            waker.wake().unwrap();
+            assert!(poller.pull_events(1000).unwrap().is_empty());
+            let start = std::time::Instant::now();
+            assert!(poller.pull_events(50).unwrap().is_empty());
+            assert!(start.elapsed() >= Duration::from_millis(50));
+            assert_eq!(poller.len(), 1);
+            let poller = Arc::new(poller);
+            let t = {
+                let poller = Arc::clone(&poller);
+                std::thread::spawn(move || poller.pull_events(-1).unwrap().len())
+            };
+            std::thread::sleep(Duration::from_millis(20));
+            let w = waker.clone();
+            std::thread::spawn(move || w.wake().unwrap())
+                .join()
+                .unwrap();
+            assert_eq!(t.join().unwrap(), 0);
+            close(&[rfd, wfd]);
+        }
+    }
+
+    #[test]
+    fn test_poller_registry() {
+        use std::os::unix::io::AsRawFd;
+        use std::os::unix::net::UnixStream;
+        for backend in backends() {
+            let (rfd, wfd) = pipe();
+            let poller = Arc::new(Poller::with_backend(backend));
+            let registry = poller.registry();
+            let t = {
+                let poller = Arc::clone(&poller);
+                std::thread::spawn(move || poller.pull_events(-1).unwrap()[0].3)
+            };
+            // 等待中的线程能收到其他线程新添加的文件描述符的事件。
+            std::thread::sleep(Duration::from_millis(20));
+            let r = registry.clone();
+            let token = std::thread::spawn(move || r.add(wfd, Events::new().write()).unwrap())
+                .join()
+                .unwrap();
+            assert_eq!(t.join().unwrap(), token);
+            assert!(poller.contains(wfd));
+            assert!(poller.context(wfd).is_none());
+            registry.remove(wfd).unwrap();
+            assert!(poller.is_empty());
+            assert!(poller.pull_events(0).unwrap().is_empty());
+            // `Poller` 销毁后添加失败，持有所有权的文件描述符被关闭。
+            let (a, _b) = UnixStream::pair().unwrap();
+            let fd = a.as_raw_fd();
+            registry.add_owned(a, Events::new().write()).unwrap();
+            drop(Arc::try_unwrap(poller).unwrap());
+            assert!(!is_open(fd));
+            let e = registry.add(rfd, Events::new().read()).unwrap_err();
+            assert_eq!(i32::from(e), libc::EBADF);
+            close(&[rfd, wfd]);
+        }
+    }
+
+    #[test]
+    fn test_poller_remove_while_waiting() {
+        for backend in backends() {
+            let (rfd, wfd) = pipe();
+            let poller = Arc::new(Poller::with_backend(backend));
+            let registry = poller.registry();
+            registry.add(rfd, Events::new().read()).unwrap();
+            let t = {
+                let poller = Arc::clone(&poller);
+                std::thread::spawn(move || {
+                    let start = Instant::now();
+                    let n = poller.pull_events(200).unwrap().len();
+                    (n, start.elapsed())
+                })
+            };
+            // 等待期间移除并关闭的文件描述符不会使等待提前返回。
+            std::thread::sleep(Duration::from_millis(20));
+            registry.remove(rfd).unwrap();
+            close(&[wfd]);
+            let (n, elapsed) = t.join().unwrap();
+            assert_eq!(n, 0);
+            assert!(elapsed >= Duration::from_millis(200), "{:?}", elapsed);
+            close(&[rfd]);
+        }
+    }
+
+    #[test]
+    fn test_poller_add_timeout() {
+        for backend in backends() {
+            let (rfd, wfd) = pipe();
+            let mut poller = Poller::<u32>::with_backend_typed(backend).max_events(2);
+            let t = poller.add(rfd, Events::new().read(), Some(0)).unwrap();
+            let a = poller.add_timeout(Duration::from_millis(30), Some(1));
+            // 定时器的 `Token` 与监视项的不会相同。
+            assert_ne!(Token::from(a), t);
+            let b = poller.add_timeout(Duration::from_millis(10), Some(2));
+            let c = poller.add_timeout(Duration::from_millis(20), Some(3));
+            assert!(poller.cancel(c));
+            assert!(!poller.cancel(c));
+            // 等待时间被缩短到定时器的到期时间，且不会提前到期。
+            let start = std::time::Instant::now();
+            let events = poller.pull_events(1000).unwrap();
+            assert_eq!(events.len(), 1);
+            assert_eq!(events[0].0, -1);
+            assert!(events[0].1.has_timeout());
+            assert_eq!(events[0].2, Some(&2));
+            assert_eq!(events[0].3, Token::from(b));
+            assert!(start.elapsed() >= Duration::from_millis(10));
+            let events = poller.pull_events(-1).unwrap();
+            assert_eq!(events[0].3, Token::from(a));
+            assert!(start.elapsed() >= Duration::from_millis(30));
+            assert!(start.elapsed() < Duration::from_millis(500));
+            assert!(!poller.cancel(a));
+            assert!(poller.pull_events(50).unwrap().is_empty());
+            // 与 I/O 事件一起上报，超出 `max_events` 的留到下次。
+            assert_eq!(unsafe { libc::write(wfd, b"x".as_ptr() as *const _, 1) }, 1);
+            for ctx in 1..4 {
+                poller.add_timeout(Duration::ZERO, Some(ctx));
+            }
+            std::thread::sleep(Duration::from_millis(2));
+            let events = poller.pull_events(1000).unwrap();
+            assert_eq!(events.len(), 2);
+            assert_eq!(events[0].2, Some(&0));
+            assert!(events[1].1.has_timeout());
+            let n = poller
+                .dispatch(1000, |fd, events, ctx, _token| {
+                    if events.has_timeout() {
+                        assert_eq!(fd, -1);
+                        *ctx.unwrap() += 10;
+                    }
+                })
+                .unwrap();
+            assert_eq!(n, 2);
+            let events = poller.pull_events(0).unwrap();
+            assert_eq!(events.len(), 2);
+            assert!(events.iter().any(|x| x.1.has_timeout()));
+            assert_eq!(events[1].2, Some(&3));
+            close(&[rfd, wfd]);
+        }
+    }
+
+    #[test]
+    fn test_poller_add_owned() {
+        use std::os::unix::io::{FromRawFd, OwnedFd};
+        for backend in backends() {
+            let (rfd, wfd) = pipe();
+            let mut poller = Poller::with_backend(backend);
+            let r = unsafe { OwnedFd::from_raw_fd(rfd) };
+            let w = unsafe { OwnedFd::from_raw_fd(wfd) };
+            poller.add_owned(r, Events::new().read(), None).unwrap();
+            poller.add_owned(w, Events::new().write(), None).unwrap();
+            assert_eq!(poller.pull_events(1000).unwrap().len(), 1);
+            poller.remove(wfd).unwrap();
+            assert!(!is_open(wfd));
+            assert!(is_open(rfd));
+            drop(poller);
+            assert!(!is_open(rfd));
+        }
+    }
+
+    #[test]
+    fn test_poller_hang_up() {
+        let backends: Vec<Box<dyn Backend>> = vec![
+            #[cfg(target_os = "linux")]
+            Box::new(epoll::Epoll::new().unwrap()),
+            Box::new(poll::Poll::new()),
+        ];
+        for backend in backends {
+            let (rfd, wfd) = pipe();
+            let mut poller = Poller::with_backend(backend);
+            poller.add(rfd, Events::new().read(), None).unwrap();
+            close(&[wfd]);
+            let events = poller.pull_events(1000).unwrap();
+            assert_eq!(events.len(), 1);
+            assert!(events[0].1.has_hang_up(), "{:?}", poller);
+            poller.remove(rfd).unwrap();
+            close(&[rfd]);
+        }
+    }
+
+    #[test]
+    fn test_poller_edge_triggered() {
+        let (rfd, wfd) = pipe();
+        let events = Events::new().read().edge_triggered();
+        #[cfg(target_os = "linux")]
+        {
+            // 边沿触发只在状态变化时上报一次，未读取数据也不会重复上报。
+            let mut poller = Poller::with_backend(Box::new(epoll::Epoll::new().unwrap()));
+            poller.add(rfd, events, None).unwrap();
+            assert!(poller.pull_events(0).unwrap().is_empty());
+            assert_eq!(unsafe { libc::write(wfd, b"x".as_ptr() as *const _, 1) }, 1);
+            let events = poller.pull_events(1000).unwrap();
+            assert_eq!(events.len(), 1);
+            assert!(events[0].1.has_read());
+            assert!(poller.pull_events(50).unwrap().is_empty());
+            assert_eq!(unsafe { libc::write(wfd, b"x".as_ptr() as *const _, 1) }, 1);
+            assert_eq!(poller.pull_events(1000).unwrap().len(), 1);
+            poller.remove(rfd).unwrap();
+        }
+        // 不支持边沿触发的后端拒绝注册及修改。
+        let backends: Vec<Box<dyn Backend>> =
+            vec![Box::new(poll::Poll::new()), Box::new(select::Select::new())];
+        for backend in backends {
+            let mut poller = Poller::with_backend(backend);
+            assert_eq!(
+                poller.add(rfd, events, None),
+                Err(SysError::from(libc::EINVAL))
+            );
+            poller.add(rfd, Events::new().read(), None).unwrap();
+            assert_eq!(
+                poller.modify(rfd, events),
+                Err(SysError::from(libc::EINVAL))
+            );
+        }
+        close(&[rfd, wfd]);
+    }
+
+    #[derive(Debug, Default)]
+    struct FakeBackend {
+        fds: std::sync::Mutex<Vec<(i32, u64)>>,
+    }
+
+    impl Backend for FakeBackend {
+        fn register(&self, fd: i32, _events: Events, data: u64) -> Result<(), SysError> {
+            self.fds.lock().unwrap().push((fd, data));
+            Ok(())
+        }
+
+        fn modify(&self, _fd: i32, _events: Events, _data: u64) -> Result<(), SysError> {
+            Ok(())
+        }
+
+        fn deregister(&self, fd: i32) -> Result<(), SysError> {
+            self.fds.lock().unwrap().retain(|x| x.0 != fd);
+            Ok(())
+        }
+
+        fn wait(
+            &self,
+            events: &mut [RawEvent],
+            _timeout: Option<Duration>,
+        ) -> Result<usize, SysError> {
+            let fds = self.fds.lock().unwrap();
+            let n = fds.len().min(events.len());
+            for (dst, src) in events.iter_mut().zip(fds.iter()) {
+                *dst = (src.1, Events::new().read());
+            }
+            Ok(n)
+        }
+    }
+
+    #[test]
+    fn test_poller_fake_backend() {
+        let mut poller = Poller::with_backend(Box::new(FakeBackend::default()));
+        poller.add(100, Events::new().read(), None).unwrap();
+        let events = poller.pull_events(-1).unwrap();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].0, 100);
+        assert!(events[0].1.has_read());
+        poller.remove(100).unwrap();
+    }
+
+    #[test]
+    fn test_poller_errors() {
+        let (rfd, wfd) = pipe();
+        let mut poller = Poller::new().unwrap();
+        assert_eq!(
+            poller.add(-1, Events::new().read(), None),
+            Err(SysError::from(libc::EBADF))
+        );
+        poller.add(rfd, Events::new().read(), None).unwrap();
+        assert_eq!(
+            poller.add(rfd, Events::new().read(), None),
+            Err(SysError::from(libc::EEXIST))
+        );
+        assert_eq!(poller.remove(wfd), Err(SysError::from(libc::ENOENT)));
+        poller.remove(rfd).unwrap();
+        close(&[rfd, wfd]);
+    }
+
+    #[test]
+    fn test_poller_remove_closed() {
+        use std::os::unix::io::{AsRawFd, FromRawFd, OwnedFd};
+        for backend in backends() {
+            let (rfd, wfd) = pipe();
+            let mut poller = Poller::<u32>::with_backend_typed(backend);
+            let registry = poller.registry();
+            poller.add(rfd, Events::new().read(), Some(1)).unwrap();
+            let w = unsafe { OwnedFd::from_raw_fd(libc::dup(wfd)) };
+            let dfd = w.as_raw_fd();
+            registry.add_owned(w, Events::new().write()).unwrap();
+            // 未经移除就关闭的文件描述符，移除时即使后端报错也不会遗留在监视列表中。
+            close(&[rfd, wfd]);
+            let _ = poller.remove(rfd);
+            assert!(!poller.contains(rfd));
+            assert!(poller.context(rfd).is_none());
+            let _ = registry.remove(dfd);
+            assert!(!poller.contains(dfd));
+            assert!(!is_open(dfd));
+            assert!(poller.is_empty());
+            assert_eq!(poller.iter().count(), 0);
+        }
+    }
+}
@@ -0,0 +1,129 @@
+//! 基于 `pidfd_open(2)` 的子进程退出通知。
+//!
+//! 需要 Linux 5.3 及以上版本，`waitid(P_PIDFD)` 需要 Linux 5.4 及以上版本。
+use crate::SysError;
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use std::os::unix::process::ExitStatusExt;
+use std::process::{Child, ExitStatus};
+
+/// 定义子进程监视器。
+///
+/// 持有子进程的 pidfd，子进程退出时文件描述符变为可读，可以与其他 `fd` 一起添加到 `Poller` 中，
+/// 收到可读事件后调用 `try_wait` 回收子进程并获取退出状态。
+///
+/// **注意：** `try_wait` 会回收子进程，之后对同一个 `Child` 调用 `wait` 或 `try_wait` 会返回错误。
+///
+/// # Examples
+///
+/// ```
+/// use poller::process::ChildWatcher;
+/// use poller::{Events, Poller};
+/// use std::process::Command;
+/// let mut poller = Poller::new().unwrap();
+/// let child = Command::new("true").spawn().unwrap();
+/// let watcher = ChildWatcher::from_child(&child).unwrap();
+/// let _reg = poller.add_fd(&watcher, Events::new().read(), None).unwrap();
+/// assert_eq!(poller.pull_events(5000).unwrap().len(), 1);
+/// assert!(watcher.try_wait().unwrap().unwrap().success());
+/// ```
+#[derive(Debug)]
+pub struct ChildWatcher {
+    fd: OwnedFd,
+    pid: i32,
+}
+
+impl AsRawFd for ChildWatcher {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl AsFd for ChildWatcher {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl ChildWatcher {
+    /// 为指定 PID 的子进程创建监视器。
+    ///
+    /// 只能回收当前进程的子进程，其他进程的 pidfd 在 `try_wait` 时返回 `ECHILD`。
+    pub fn new(pid: i32) -> Result<Self, SysError> {
+        let fd = unsafe { libc::syscall(libc::SYS_pidfd_open, pid, 0) };
+        if fd < 0 {
+            Err(SysError::last())
+        } else {
+            let fd = fd as i32;
+            unsafe { libc::fcntl(fd, libc::F_SETFL, libc::O_NONBLOCK) };
+            Ok(Self {
+                fd: unsafe { OwnedFd::from_raw_fd(fd) },
+                pid,
+            })
+        }
+    }
+
+    /// 为 `Child` 创建监视器。
+    pub fn from_child(child: &Child) -> Result<Self, SysError> {
+        Self::new(child.id() as i32)
+    }
+
+    /// 返回监视的子进程 PID。
+    pub fn pid(&self) -> i32 {
+        self.pid
+    }
+
+    /// 回收已退出的子进程并返回其退出状态，子进程仍在运行时返回 `None`。
+    pub fn try_wait(&self) -> Result<Option<ExitStatus>, SysError> {
+        let mut info: libc::siginfo_t = unsafe { std::mem::zeroed() };
+        let options = libc::WEXITED | libc::WNOHANG;
+        let pidfd = self.fd.as_raw_fd() as libc::id_t;
+        let err = unsafe { libc::waitid(libc::P_PIDFD, pidfd, &mut info, options) };
+        if err < 0 {
+            return Err(SysError::last());
+        }
+        if unsafe { info.si_pid() } == 0 {
+            return Ok(None);
+        }
+        // 转换为 `wait(2)` 的状态编码。
+        let status = unsafe { info.si_status() };
+        let status = match info.si_code {
+            libc::CLD_EXITED => (status & 0xff) << 8,
+            libc::CLD_DUMPED => status | 0x80,
+            _ => status,
+        };
+        Ok(Some(ExitStatus::from_raw(status)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{Events, Poller};
+    use std::process::Command;
+
+    #[test]
+    fn test_child_watcher() {
+        let mut poller = Poller::new().unwrap();
+        let mut child = Command::new("sh").args(["-c", "exit 3"]).spawn().unwrap();
+        let watcher = ChildWatcher::from_child(&child).unwrap();
+        assert_eq!(watcher.pid(), child.id() as i32);
+        let reg = poller.add_fd(&watcher, Events::new().read(), None).unwrap();
+        assert_eq!(poller.pull_events(5000).unwrap().len(), 1);
+        assert_eq!(watcher.try_wait().unwrap().unwrap().code(), Some(3));
+        // 已被回收，`Child` 无法再次等待。
+        assert!(child.wait().is_err());
+        drop(reg);
+        // 被信号终止的子进程。
+        let mut child = Command::new("sleep").arg("10").spawn().unwrap();
+        let watcher = ChildWatcher::new(child.id() as i32).unwrap();
+        let _reg = poller.add_fd(&watcher, Events::new().read(), None).unwrap();
+        assert!(poller.pull_events(0).unwrap().is_empty());
+        assert_eq!(watcher.try_wait().unwrap(), None);
+        child.kill().unwrap();
+        assert_eq!(poller.pull_events(5000).unwrap().len(), 1);
+        let status = watcher.try_wait().unwrap().unwrap();
+        assert_eq!(status.signal(), Some(libc::SIGKILL));
+        assert!(child.wait().is_err());
+        assert!(ChildWatcher::new(-1).is_err());
+    }
+}
@@ -1 +1,121 @@
-﻿// TODO: Add select implements
+//! 基于 `select(2)`（实际使用 `pselect(2)`）的 I/O 事件通知。
+//!
+//! 只能监视小于 `FD_SETSIZE` 的文件描述符，适用于只有少量 `fd` 的场合。
+//!
+//! 与 `poll` 后端一样不支持边沿触发，并模拟单次触发；紧急数据通过异常集合上报为
+//! `Event::Priority`，挂起及错误则体现为可读或可写。
+use crate::list::WatchList;
+use crate::signal::SigSet;
+use crate::{Backend, Events, RawEvent, SysError};
+use libc::{fd_set, pselect, FD_ISSET, FD_SET, FD_SETSIZE, FD_ZERO};
+use std::time::Duration;
+
+/// 基于 `select(2)` 的后端。
+#[derive(Debug)]
+pub struct Select {
+    list: WatchList,
+}
+
+impl Default for Select {
+    fn default() -> Self {
+        Self {
+            list: WatchList::with_limit(FD_SETSIZE),
+        }
+    }
+}
+
+impl Select {
+    /// 创建一个新的 `select(2)` 后端。
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl Backend for Select {
+    fn register(&self, fd: i32, events: Events, data: u64) -> Result<(), SysError> {
+        self.list.register(fd, events, data)
+    }
+
+    fn modify(&self, fd: i32, events: Events, data: u64) -> Result<(), SysError> {
+        self.list.modify(fd, events, data)
+    }
+
+    fn deregister(&self, fd: i32) -> Result<(), SysError> {
+        self.list.deregister(fd)
+    }
+
+    fn wait(&self, events: &mut [RawEvent], timeout: Option<Duration>) -> Result<usize, SysError> {
+        self.wait_events(events, timeout, None)
+    }
+
+    fn wait_with_sigmask(
+        &self,
+        events: &mut [RawEvent],
+        timeout: Option<Duration>,
+        sigmask: &SigSet,
+    ) -> Result<usize, SysError> {
+        self.wait_events(events, timeout, Some(sigmask))
+    }
+}
+
+impl Select {
+    fn wait_events(
+        &self,
+        events: &mut [RawEvent],
+        timeout: Option<Duration>,
+        sigmask: Option<&SigSet>,
+    ) -> Result<usize, SysError> {
+        self.list
+            .wait(events, timeout, |snapshot, waker_fd, timeout| unsafe {
+                let mut rfds: fd_set = std::mem::zeroed();
+                let mut wfds: fd_set = std::mem::zeroed();
+                let mut efds: fd_set = std::mem::zeroed();
+                FD_ZERO(&mut rfds);
+                FD_ZERO(&mut wfds);
+                FD_ZERO(&mut efds);
+                let mut nfds = 0;
+                for &(fd, events, _) in snapshot.iter() {
+                    if events.has_read() {
+                        FD_SET(fd, &mut rfds);
+                    }
+                    if events.has_write() {
+                        FD_SET(fd, &mut wfds);
+                    }
+                    if events.has_priority() {
+                        FD_SET(fd, &mut efds);
+                    }
+                    nfds = nfds.max(fd + 1);
+                }
+                if let Some(fd) = waker_fd {
+                    FD_SET(fd, &mut rfds);
+                    nfds = nfds.max(fd + 1);
+                }
+                let ts = timeout.map(crate::to_timespec);
+                let pts = ts
+                    .as_ref()
+                    .map_or(std::ptr::null(), |x| x as *const libc::timespec);
+                let pmask = sigmask.map_or(std::ptr::null(), |x| x.as_ptr());
+                if pselect(nfds, &mut rfds, &mut wfds, &mut efds, pts, pmask) < 0 {
+                    return Err(SysError::last());
+                }
+                let woken = waker_fd.is_some_and(|fd| FD_ISSET(fd, &rfds));
+                let mut ready = Vec::new();
+                for &(fd, interest, data) in snapshot.iter() {
+                    let mut ev = Events::new();
+                    if interest.has_read() && FD_ISSET(fd, &rfds) {
+                        ev = ev.read();
+                    }
+                    if interest.has_write() && FD_ISSET(fd, &wfds) {
+                        ev = ev.write();
+                    }
+                    if interest.has_priority() && FD_ISSET(fd, &efds) {
+                        ev = ev.priority();
+                    }
+                    if !ev.is_none() {
+                        ready.push((fd, data, ev));
+                    }
+                }
+                Ok((ready, woken))
+            })
+    }
+}
@@ -0,0 +1,267 @@
+//! 信号集合及基于 `signalfd(2)` 的信号接收。
+//!
+use crate::SysError;
+#[cfg(any(target_os = "linux", target_os = "android"))]
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+
+/// 定义信号集合。
+///
+/// # Examples
+///
+/// ```
+/// use poller::signal::SigSet;
+/// let set = SigSet::empty().with(libc::SIGTERM).with(libc::SIGHUP);
+/// assert!(set.contains(libc::SIGTERM));
+/// ```
+#[derive(Clone, Copy)]
+pub struct SigSet(libc::sigset_t);
+
+impl std::fmt::Debug for SigSet {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_set()
+            .entries((1..65).filter(|x| self.contains(*x)))
+            .finish()
+    }
+}
+
+impl Default for SigSet {
+    fn default() -> Self {
+        Self::empty()
+    }
+}
+
+impl SigSet {
+    /// 创建一个空的信号集合。
+    pub fn empty() -> Self {
+        unsafe {
+            let mut set: libc::sigset_t = std::mem::zeroed();
+            libc::sigemptyset(&mut set);
+            Self(set)
+        }
+    }
+
+    /// 创建一个包含所有信号的集合。
+    pub fn full() -> Self {
+        unsafe {
+            let mut set: libc::sigset_t = std::mem::zeroed();
+            libc::sigfillset(&mut set);
+            Self(set)
+        }
+    }
+
+    /// 返回当前线程的信号掩码。
+    pub fn current() -> Result<Self, SysError> {
+        Self::empty().sigmask(libc::SIG_BLOCK)
+    }
+
+    /// 附加一个信号到集合中。
+    pub fn with(mut self, signo: i32) -> Self {
+        unsafe { libc::sigaddset(&mut self.0, signo) };
+        self
+    }
+
+    /// 从集合中移除一个信号。
+    pub fn without(mut self, signo: i32) -> Self {
+        unsafe { libc::sigdelset(&mut self.0, signo) };
+        self
+    }
+
+    /// 检查集合中是否有指定的信号。
+    pub fn contains(&self, signo: i32) -> bool {
+        unsafe { libc::sigismember(&self.0, signo) == 1 }
+    }
+
+    /// 在当前线程中阻塞集合中的信号，返回原来的信号掩码。
+    pub fn block(&self) -> Result<Self, SysError> {
+        self.sigmask(libc::SIG_BLOCK)
+    }
+
+    /// 在当前线程中解除阻塞集合中的信号，返回原来的信号掩码。
+    pub fn unblock(&self) -> Result<Self, SysError> {
+        self.sigmask(libc::SIG_UNBLOCK)
+    }
+
+    /// 返回底层 `sigset_t` 的指针。
+    pub fn as_ptr(&self) -> *const libc::sigset_t {
+        &self.0
+    }
+
+    fn sigmask(&self, how: i32) -> Result<Self, SysError> {
+        let mut old = Self::empty();
+        let err = unsafe { libc::pthread_sigmask(how, &self.0, &mut old.0) };
+        if err != 0 {
+            Err(SysError::from(err))
+        } else {
+            Ok(old)
+        }
+    }
+}
+
+/// 定义收到的信号信息。
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignalInfo {
+    /// 信号编号。
+    pub signo: i32,
+    /// 发送进程的 PID，`SIGCHLD` 时为子进程的 PID。
+    pub pid: i32,
+    /// 发送进程的真实 UID。
+    pub uid: u32,
+    /// `SIGCHLD` 时为子进程的退出码或导致其状态变化的信号。
+    pub status: i32,
+    /// 信号来源，如 `SI_USER`、`SI_QUEUE` 或 `CLD_EXITED` 等。
+    pub code: i32,
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl From<&libc::signalfd_siginfo> for SignalInfo {
+    fn from(val: &libc::signalfd_siginfo) -> Self {
+        Self {
+            signo: val.ssi_signo as i32,
+            pid: val.ssi_pid as i32,
+            uid: val.ssi_uid,
+            status: val.ssi_status,
+            code: val.ssi_code,
+        }
+    }
+}
+
+/// 定义基于 `signalfd(2)` 的信号接收器。
+///
+/// 创建时在当前线程中阻塞指定的信号，之后这些信号不再触发信号处理函数，而是使文件描述符变为可读，
+/// 可以与其他 `fd` 一起添加到 `Poller` 中，在同一个事件循环中处理。
+///
+/// 进程收到的信号可能被投递到任意一个未阻塞它的线程，因此应在创建其他线程前创建，
+/// 使所有线程都继承阻塞的信号掩码。销毁时不会解除阻塞。
+///
+/// # Examples
+///
+/// ```
+/// use poller::signal::{SigSet, Signals};
+/// use poller::{Events, Poller};
+/// let mut poller = Poller::new().unwrap();
+/// let signals = Signals::new(SigSet::empty().with(libc::SIGUSR1)).unwrap();
+/// let _reg = poller.add_fd(&signals, Events::new().read(), None).unwrap();
+/// unsafe { libc::raise(libc::SIGUSR1) };
+/// for (_fd, _events, _ctx, _token) in poller.pull_events(1000).unwrap() {
+///     while let Some(info) = signals.read().unwrap() {
+///         assert_eq!(info.signo, libc::SIGUSR1);
+///     }
+/// }
+/// ```
+#[cfg(any(target_os = "linux", target_os = "android"))]
+#[derive(Debug)]
+pub struct Signals {
+    fd: OwnedFd,
+    set: SigSet,
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl AsRawFd for Signals {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl AsFd for Signals {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+#[cfg(any(target_os = "linux", target_os = "android"))]
+impl Signals {
+    /// 阻塞 `set` 中的信号并创建接收这些信号的信号接收器。
+    pub fn new(set: SigSet) -> Result<Self, SysError> {
+        set.block()?;
+        let fd =
+            unsafe { libc::signalfd(-1, set.as_ptr(), libc::SFD_CLOEXEC | libc::SFD_NONBLOCK) };
+        if fd < 0 {
+            Err(SysError::last())
+        } else {
+            Ok(Self {
+                fd: unsafe { OwnedFd::from_raw_fd(fd) },
+                set,
+            })
+        }
+    }
+
+    /// 返回接收的信号集合。
+    pub fn signals(&self) -> &SigSet {
+        &self.set
+    }
+
+    /// 读取一个待处理的信号，没有待处理的信号时返回 `None`。
+    ///
+    /// 同一个标准信号在处理前多次发送只会读取到一次。
+    pub fn read(&self) -> Result<Option<SignalInfo>, SysError> {
+        let mut info: libc::signalfd_siginfo = unsafe { std::mem::zeroed() };
+        let size = std::mem::size_of::<libc::signalfd_siginfo>();
+        let n = unsafe { libc::read(self.fd.as_raw_fd(), &mut info as *mut _ as *mut _, size) };
+        if n < 0 {
+            match SysError::last() {
+                e if i32::from(e) == libc::EAGAIN => Ok(None),
+                e => Err(e),
+            }
+        } else {
+            Ok(Some(SignalInfo::from(&info)))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sigset() {
+        let set = SigSet::empty().with(libc::SIGTERM).with(libc::SIGHUP);
+        assert!(set.contains(libc::SIGTERM));
+        assert!(set.contains(libc::SIGHUP));
+        assert!(!set.contains(libc::SIGINT));
+        assert!(!set.without(libc::SIGTERM).contains(libc::SIGTERM));
+        assert!(SigSet::full().contains(libc::SIGINT));
+        assert_eq!(format!("{:?}", set), "{1, 15}");
+    }
+
+    #[test]
+    fn test_sigset_block() {
+        std::thread::spawn(|| {
+            let set = SigSet::empty().with(libc::SIGUSR2);
+            let old = set.block().unwrap();
+            assert!(!old.contains(libc::SIGUSR2));
+            assert!(SigSet::current().unwrap().contains(libc::SIGUSR2));
+            set.unblock().unwrap();
+            assert!(!SigSet::current().unwrap().contains(libc::SIGUSR2));
+        })
+        .join()
+        .unwrap();
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    #[test]
+    fn test_signals() {
+        use crate::{Events, Poller};
+        std::thread::spawn(|| {
+            let set = SigSet::empty().with(libc::SIGUSR1);
+            let signals = Signals::new(set).unwrap();
+            assert!(SigSet::current().unwrap().contains(libc::SIGUSR1));
+            assert_eq!(signals.read().unwrap(), None);
+            let mut poller = Poller::new().unwrap();
+            let _reg = poller.add_fd(&signals, Events::new().read(), None).unwrap();
+            assert!(poller.pull_events(0).unwrap().is_empty());
+            unsafe { libc::raise(libc::SIGUSR1) };
+            assert_eq!(poller.pull_events(1000).unwrap().len(), 1);
+            let info = signals.read().unwrap().unwrap();
+            assert_eq!(info.signo, libc::SIGUSR1);
+            assert_eq!(info.pid, unsafe { libc::getpid() });
+            assert_eq!(info.uid, unsafe { libc::getuid() });
+            assert_eq!(info.code, libc::SI_TKILL);
+            assert_eq!(signals.read().unwrap(), None);
+            set.unblock().unwrap();
+        })
+        .join()
+        .unwrap();
+    }
+}
@@ -0,0 +1,241 @@
+//! 基于 `timerfd(2)` 的定时器。
+//!
+//! 定时器到期时文件描述符变为可读，可以像其他 `fd` 一样添加到 `Poller` 中监视。
+use crate::{to_timespec, SysError};
+use std::os::unix::io::{AsFd, AsRawFd, BorrowedFd, FromRawFd, OwnedFd, RawFd};
+use std::time::Duration;
+
+/// 定义定时器使用的时钟。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Clock {
+    /// 单调时钟，不受系统时间修改影响，系统挂起期间停止计时。
+    Monotonic,
+    /// 与 `Monotonic` 相同，但包括系统挂起的时间。
+    BootTime,
+    /// 系统实时时钟，即自 1970-01-01 00:00:00 UTC 以来的时间，会受系统时间修改影响。
+    RealTime,
+}
+
+impl From<Clock> for libc::clockid_t {
+    fn from(val: Clock) -> Self {
+        match val {
+            Clock::Monotonic => libc::CLOCK_MONOTONIC,
+            Clock::BootTime => libc::CLOCK_BOOTTIME,
+            Clock::RealTime => libc::CLOCK_REALTIME,
+        }
+    }
+}
+
+impl Clock {
+    /// 返回时钟的当前时间，用于计算绝对到期时间。
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use poller::timer::{Clock, Deadline};
+    /// use std::time::Duration;
+    /// let deadline = Deadline::At(Clock::Monotonic.now().unwrap() + Duration::from_secs(1));
+    /// ```
+    pub fn now(self) -> Result<Duration, SysError> {
+        let mut ts: libc::timespec = unsafe { std::mem::zeroed() };
+        if unsafe { libc::clock_gettime(self.into(), &mut ts) } < 0 {
+            Err(SysError::last())
+        } else {
+            Ok(from_timespec(&ts))
+        }
+    }
+}
+
+/// 定义定时器的首次到期时间。
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Deadline {
+    /// 相对于设置时刻的到期时间。
+    After(Duration),
+    /// 以定时器时钟表示的绝对到期时间，参见 `Clock::now`，已经过去时立即到期。
+    At(Duration),
+}
+
+fn from_timespec(ts: &libc::timespec) -> Duration {
+    Duration::new(ts.tv_sec as u64, ts.tv_nsec as u32)
+}
+
+/// 定义基于 `timerfd(2)` 的定时器。
+///
+/// 定时器到期时文件描述符变为可读，`read` 返回自上次读取以来到期的次数并复位可读状态，
+/// 周期定时器的次数大于 1 时表示处理不及时错过了到期。
+///
+/// # Examples
+///
+/// ```
+/// use poller::timer::{Clock, Timer};
+/// use poller::{Events, Poller};
+/// use std::time::Duration;
+/// let mut poller = Poller::new().unwrap();
+/// let timer = Timer::new(Clock::Monotonic).unwrap();
+/// let _reg = poller.add_fd(&timer, Events::new().read(), None).unwrap();
+/// timer.set_interval(Duration::from_millis(10)).unwrap();
+/// for _ in 0..3 {
+///     for (_fd, _events, _ctx, _token) in poller.pull_events(1000).unwrap() {
+///         println!("Expirations: {}", timer.read().unwrap());
+///     }
+/// }
+/// ```
+#[derive(Debug)]
+pub struct Timer {
+    fd: OwnedFd,
+    clock: Clock,
+}
+
+impl AsRawFd for Timer {
+    fn as_raw_fd(&self) -> RawFd {
+        self.fd.as_raw_fd()
+    }
+}
+
+impl AsFd for Timer {
+    fn as_fd(&self) -> BorrowedFd<'_> {
+        self.fd.as_fd()
+    }
+}
+
+impl Timer {
+    /// 创建一个使用指定时钟、未启动的定时器。
+    pub fn new(clock: Clock) -> Result<Self, SysError> {
+        let fd =
+            unsafe { libc::timerfd_create(clock.into(), libc::TFD_CLOEXEC | libc::TFD_NONBLOCK) };
+        if fd < 0 {
+            Err(SysError::last())
+        } else {
+            Ok(Self {
+                fd: unsafe { OwnedFd::from_raw_fd(fd) },
+                clock,
+            })
+        }
+    }
+
+    /// 返回定时器使用的时钟。
+    pub fn clock(&self) -> Clock {
+        self.clock
+    }
+
+    /// 启动定时器，在 `deadline` 首次到期，`interval` 不为 `None` 时之后每隔 `interval` 到期一次。
+    ///
+    /// 重新设置会替换原有的到期时间并清除未读取的到期次数。
+    pub fn set(&self, deadline: Deadline, interval: Option<Duration>) -> Result<(), SysError> {
+        let (value, flags) = match deadline {
+            Deadline::After(d) => (d, 0),
+            Deadline::At(t) => (t, libc::TFD_TIMER_ABSTIME),
+        };
+        // 首次到期时间为零表示停止定时器，改为最小的非零值使其立即到期。
+        let spec = libc::itimerspec {
+            it_interval: to_timespec(interval.unwrap_or_default()),
+            it_value: to_timespec(value.max(Duration::from_nanos(1))),
+        };
+        let err = unsafe {
+            libc::timerfd_settime(self.fd.as_raw_fd(), flags, &spec, std::ptr::null_mut())
+        };
+        if err < 0 {
+            Err(SysError::last())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// 启动一个在 `after` 后到期一次的定时器。
+    pub fn set_after(&self, after: Duration) -> Result<(), SysError> {
+        self.set(Deadline::After(after), None)
+    }
+
+    /// 启动一个每隔 `interval` 到期一次的周期定时器。
+    pub fn set_interval(&self, interval: Duration) -> Result<(), SysError> {
+        self.set(Deadline::After(interval), Some(interval))
+    }
+
+    /// 停止定时器并清除未读取的到期次数。
+    pub fn disarm(&self) -> Result<(), SysError> {
+        let spec: libc::itimerspec = unsafe { std::mem::zeroed() };
+        let err =
+            unsafe { libc::timerfd_settime(self.fd.as_raw_fd(), 0, &spec, std::ptr::null_mut()) };
+        if err < 0 {
+            Err(SysError::last())
+        } else {
+            Ok(())
+        }
+    }
+
+    /// 返回距离下次到期的时间及周期，定时器未启动时返回 `None`。
+    pub fn remaining(&self) -> Result<Option<(Duration, Option<Duration>)>, SysError> {
+        let mut spec: libc::itimerspec = unsafe { std::mem::zeroed() };
+        if unsafe { libc::timerfd_gettime(self.fd.as_raw_fd(), &mut spec) } < 0 {
+            return Err(SysError::last());
+        }
+        let value = from_timespec(&spec.it_value);
+        let interval = from_timespec(&spec.it_interval);
+        if value.is_zero() {
+            Ok(None)
+        } else {
+            Ok(Some((value, Some(interval).filter(|x| !x.is_zero()))))
+        }
+    }
+
+    /// 读取并清零自上次读取以来到期的次数，尚未到期时返回 0。
+    ///
+    /// 收到可读事件后应调用此函数，否则定时器会一直处于可读状态。
+    pub fn read(&self) -> Result<u64, SysError> {
+        let mut buf = [0u8; 8];
+        let n = unsafe { libc::read(self.fd.as_raw_fd(), buf.as_mut_ptr() as *mut _, buf.len()) };
+        if n < 0 {
+            match SysError::last() {
+                e if i32::from(e) == libc::EAGAIN => Ok(0),
+                e => Err(e),
+            }
+        } else {
+            Ok(u64::from_ne_bytes(buf))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_timer_one_shot() {
+        for clock in [Clock::Monotonic, Clock::BootTime, Clock::RealTime] {
+            let timer = Timer::new(clock).unwrap();
+            assert_eq!(timer.remaining().unwrap(), None);
+            assert_eq!(timer.read().unwrap(), 0);
+            timer.set_after(Duration::from_millis(10)).unwrap();
+            let (value, interval) = timer.remaining().unwrap().unwrap();
+            assert!(value <= Duration::from_millis(10));
+            assert_eq!(interval, None);
+            std::thread::sleep(Duration::from_millis(20));
+            assert_eq!(timer.read().unwrap(), 1);
+            assert_eq!(timer.read().unwrap(), 0);
+            // 已经过去的绝对时间立即到期。
+            let now = clock.now().unwrap();
+            timer.set(Deadline::At(now), None).unwrap();
+            std::thread::sleep(Duration::from_millis(1));
+            assert_eq!(timer.read().unwrap(), 1);
+            timer.set(Deadline::After(Duration::ZERO), None).unwrap();
+            std::thread::sleep(Duration::from_millis(1));
+            assert_eq!(timer.read().unwrap(), 1);
+        }
+    }
+
+    #[test]
+    fn test_timer_interval() {
+        let timer = Timer::new(Clock::Monotonic).unwrap();
+        timer.set_interval(Duration::from_millis(5)).unwrap();
+        assert_eq!(
+            timer.remaining().unwrap().unwrap().1,
+            Some(Duration::from_millis(5))
+        );
+        // 未及时读取时返回累计的到期次数。
+        std::thread::sleep(Duration::from_millis(30));
+        assert!(timer.read().unwrap() >= 5);
+        timer.disarm().unwrap();
+        assert_eq!(timer.remaining().unwrap(), None);
+        assert_eq!(timer.read().unwrap(), 0);
+    }
+}
@@ -0,0 +1,113 @@
+//! 跨线程唤醒器。
+//!
+use crate::SysError;
+use std::sync::Arc;
+
+/// 定义唤醒用的文件描述符，Linux 平台上使用 `eventfd(2)`，其他平台上使用管道。
+#[derive(Debug)]
+struct WakerFd {
+    read_fd: i32,
+    write_fd: i32,
+}
+
+impl Drop for WakerFd {
+    fn drop(&mut self) {
+        unsafe {
+            libc::close(self.read_fd);
+            if self.write_fd != self.read_fd {
+                libc::close(self.write_fd);
+            }
+        }
+    }
+}
+
+impl WakerFd {
+    #[cfg(any(target_os = "linux", target_os = "android"))]
+    fn new() -> Result<Self, SysError> {
+        let fd = unsafe { libc::eventfd(0, libc::EFD_CLOEXEC | libc::EFD_NONBLOCK) };
+        if fd < 0 {
+            Err(SysError::last())
+        } else {
+            Ok(Self {
+                read_fd: fd,
+                write_fd: fd,
+            })
+        }
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "android")))]
+    fn new() -> Result<Self, SysError> {
+        let mut fds = [0; 2];
+        if unsafe { libc::pipe(fds.as_mut_ptr()) } < 0 {
+            return Err(SysError::last());
+        }
+        let this = Self {
+            read_fd: fds[0],
+            write_fd: fds[1],
+        };
+        for fd in fds.iter() {
+            unsafe {
+                libc::fcntl(*fd, libc::F_SETFD, libc::FD_CLOEXEC);
+                libc::fcntl(*fd, libc::F_SETFL, libc::O_NONBLOCK);
+            }
+        }
+        Ok(this)
+    }
+}
+
+/// 定义跨线程唤醒器。
+///
+/// 由 `Poller::waker` 创建，可以克隆并在任意线程中调用 `wake` 使正在等待的 `pull_events`
+/// 立即返回，唤醒本身不会出现在拉取到的事件中。
+///
+/// # Examples
+///
+/// ```
+/// use poller::Poller;
+/// use std::sync::Arc;
+/// let mut poller = Poller::new().unwrap();
+/// let waker = poller.waker().unwrap();
+/// let poller = Arc::new(poller);
+/// let t = std::thread::spawn(move || poller.pull_events(-1).unwrap().len());
+/// waker.wake().unwrap();
+/// assert_eq!(t.join().unwrap(), 0);
+/// ```
+#[derive(Clone, Debug)]
+pub struct Waker {
+    inner: Arc<WakerFd>,
+}
+
+impl Waker {
+    pub(crate) fn new() -> Result<Self, SysError> {
+        Ok(Self {
+            inner: Arc::new(WakerFd::new()?),
+        })
+    }
+
+    /// 返回需要监视可读事件的文件描述符。
+    pub(crate) fn fd(&self) -> i32 {
+        self.inner.read_fd
+    }
+
+    /// 唤醒正在等待的 `Poller`，在没有线程等待时下次等待会立即返回。
+    pub fn wake(&self) -> Result<(), SysError> {
+        let buf = 1u64.to_ne_bytes();
+        let n = unsafe { libc::write(self.inner.write_fd, buf.as_ptr() as *const _, buf.len()) };
+        if n < 0 {
+            // 计数器已满或管道已满时说明已有未处理的唤醒。
+            match SysError::last() {
+                e if i32::from(e) == libc::EAGAIN => Ok(()),
+                e => Err(e),
+            }
+        } else {
+            Ok(())
+        }
+    }
+
+    /// 读空累积的唤醒。
+    pub(crate) fn drain(&self) {
+        let mut buf = [0u8; 64];
+        while unsafe { libc::read(self.inner.read_fd, buf.as_mut_ptr() as *mut _, buf.len()) } > 0 {
+        }
+    }
+}
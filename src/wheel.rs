@@ -0,0 +1,376 @@
+//! 分层时间轮。
+//!
+//! 以毫秒为刻度，共 6 层，每层 64 个槽位，第 `n` 层每个槽位跨越 `64^n` 个刻度。
+//! 定时器按到期刻度与当前刻度的最高不同位放入对应的层，随时间推进逐层下移，
+//! 添加、取消均为 O(1)，取消时立即从槽位中移除。
+use crate::Token;
+use std::collections::VecDeque;
+use std::time::{Duration, Instant};
+
+/// 每层的槽位数量。
+const SLOTS: usize = 64;
+
+/// 层数。
+const LEVELS: usize = 6;
+
+/// 最高层转一轮的刻度数，约 795 天，更远的定时器会在最高层停留多轮。
+const MAX_TICKS: u64 = 1 << (6 * LEVELS);
+
+/// 定时器 `Token` 索引中的保留位，监视项的索引不会使用，使两者不会相同。
+pub(crate) const TIMER_FLAG: usize = 1 << 31;
+
+/// 定义定时器标识。
+///
+/// 由 `Poller::add_timeout` 返回，与定时器到期事件中的 `Token` 对应。
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct TimerId(Token);
+
+impl TimerId {
+    /// 从定时器到期事件中的 `Token` 还原。
+    pub(crate) fn from_token(token: Token) -> Self {
+        Self(Token::new(token.index() & !TIMER_FLAG, token.generation()))
+    }
+
+    /// 返回以时间轮槽位为索引的 `Token`，用于保存上下文。
+    pub(crate) fn key(self) -> Token {
+        self.0
+    }
+
+    pub(crate) fn index(self) -> usize {
+        self.0.index()
+    }
+
+    pub(crate) fn generation(self) -> u32 {
+        self.0.generation()
+    }
+}
+
+impl From<TimerId> for Token {
+    fn from(val: TimerId) -> Self {
+        Token::new(val.0.index() | TIMER_FLAG, val.0.generation())
+    }
+}
+
+/// 定义定时器状态。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum State {
+    Vacant,
+    /// 等待到期，值为到期刻度。
+    Pending(u64),
+    /// 已上报到期，等待回收。
+    Fired,
+}
+
+#[derive(Debug)]
+struct Slot {
+    generation: u32,
+    state: State,
+    /// 所在的层、槽位及在槽位中的位置，位于就绪列表中时为 `None`。
+    place: Option<(usize, usize, usize)>,
+}
+
+/// 定义时间轮的一层。
+#[derive(Debug)]
+struct Level {
+    /// 非空槽位的位图。
+    occupied: u64,
+    /// 槽位中的定时器。
+    slots: [Vec<TimerId>; SLOTS],
+}
+
+/// 定义分层时间轮。
+#[derive(Debug)]
+pub(crate) struct Wheel {
+    start: Instant,
+    /// 已推进到的刻度。
+    elapsed: u64,
+    levels: Vec<Level>,
+    timers: Vec<Slot>,
+    free: Vec<usize>,
+    /// 已到期尚未上报的定时器，取消的定时器在取出时丢弃。
+    ready: VecDeque<TimerId>,
+    /// 已上报尚未回收的定时器。
+    fired: Vec<TimerId>,
+}
+
+impl Wheel {
+    pub(crate) fn new() -> Self {
+        Self {
+            start: Instant::now(),
+            elapsed: 0,
+            levels: (0..LEVELS)
+                .map(|_| Level {
+                    occupied: 0,
+                    slots: std::array::from_fn(|_| Vec::new()),
+                })
+                .collect(),
+            timers: Vec::new(),
+            free: Vec::new(),
+            ready: VecDeque::new(),
+            fired: Vec::new(),
+        }
+    }
+
+    /// 返回 `t` 所在的刻度。
+    fn tick(&self, t: Instant) -> u64 {
+        t.saturating_duration_since(self.start).as_millis() as u64
+    }
+
+    /// 添加一个在 `deadline` 到期的定时器。
+    pub(crate) fn insert(&mut self, deadline: Instant) -> TimerId {
+        // 向上取整，保证不会早于 `deadline` 到期。
+        let d = deadline.saturating_duration_since(self.start);
+        let when = d.as_nanos().div_ceil(1_000_000).min(u64::MAX as u128) as u64;
+        let index = self.free.pop().unwrap_or_else(|| {
+            self.timers.push(Slot {
+                generation: 0,
+                state: State::Vacant,
+                place: None,
+            });
+            self.timers.len() - 1
+        });
+        let slot = &mut self.timers[index];
+        slot.state = State::Pending(when);
+        let id = TimerId(Token::new(index, slot.generation));
+        self.schedule(id, when);
+        id
+    }
+
+    /// 取消一个等待到期的定时器，已到期或已取消时返回 `false`。
+    pub(crate) fn cancel(&mut self, id: TimerId) -> bool {
+        match self.timers.get(id.index()) {
+            Some(x) if x.generation == id.generation() && matches!(x.state, State::Pending(_)) => {
+                self.unlink(id.index());
+                self.release(id.index());
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// 回收已上报的定时器，返回它们的标识。
+    pub(crate) fn collect(&mut self) -> Vec<TimerId> {
+        let fired = std::mem::take(&mut self.fired);
+        for id in fired.iter() {
+            self.release(id.index());
+        }
+        fired
+    }
+
+    /// 将定时器从所在的槽位中移除，被交换到其位置的定时器同步更新位置。
+    fn unlink(&mut self, index: usize) {
+        let (level, slot, pos) = match self.timers[index].place.take() {
+            Some(x) => x,
+            None => return,
+        };
+        let level_ref = &mut self.levels[level];
+        let ids = &mut level_ref.slots[slot];
+        ids.swap_remove(pos);
+        if ids.is_empty() {
+            level_ref.occupied &= !(1 << slot);
+        }
+        if let Some(moved) = ids.get(pos) {
+            self.timers[moved.index()].place = Some((level, slot, pos));
+        }
+    }
+
+    fn release(&mut self, index: usize) {
+        let slot = &mut self.timers[index];
+        slot.state = State::Vacant;
+        slot.generation = slot.generation.wrapping_add(1);
+        self.free.push(index);
+    }
+
+    fn pending(&self, id: TimerId) -> Option<u64> {
+        match self.timers.get(id.index()) {
+            Some(x) if x.generation == id.generation() => match x.state {
+                State::Pending(when) => Some(when),
+                _ => None,
+            },
+            _ => None,
+        }
+    }
+
+    /// 将定时器放入对应的层及槽位，已到期的直接放入就绪列表。
+    fn schedule(&mut self, id: TimerId, when: u64) {
+        if when <= self.elapsed {
+            self.timers[id.index()].place = None;
+            self.ready.push_back(id);
+            return;
+        }
+        let when = when.min(self.elapsed + MAX_TICKS - 1);
+        let mut masked = (self.elapsed ^ when) | (SLOTS as u64 - 1);
+        // 跨越最高层边界的放入最高层，此时最高层的槽位作为环形使用。
+        if masked >= MAX_TICKS {
+            masked = MAX_TICKS - 1;
+        }
+        let level = (63 - masked.leading_zeros() as usize) / 6;
+        let slot = ((when >> (level * 6)) as usize) % SLOTS;
+        let ids = &mut self.levels[level].slots[slot];
+        ids.push(id);
+        self.timers[id.index()].place = Some((level, slot, ids.len() - 1));
+        self.levels[level].occupied |= 1 << slot;
+    }
+
+    /// 返回下一个需要处理的槽位及其到期刻度。
+    fn next_expiration(&self) -> Option<(usize, usize, u64)> {
+        for (index, level) in self.levels.iter().enumerate() {
+            if level.occupied == 0 {
+                continue;
+            }
+            let slot_range = 1u64 << (index * 6);
+            let level_range = slot_range * SLOTS as u64;
+            let now_slot = ((self.elapsed / slot_range) % SLOTS as u64) as u32;
+            // 最高层当前位置的槽位只存放下一轮的定时器，从其后的槽位开始查找。
+            let first = if index == LEVELS - 1 {
+                (now_slot + 1) % SLOTS as u32
+            } else {
+                now_slot
+            };
+            let zeros = level.occupied.rotate_right(first).trailing_zeros();
+            let slot = (first + zeros) as usize % SLOTS;
+            let level_start = self.elapsed & !(level_range - 1);
+            let mut deadline = level_start + slot as u64 * slot_range;
+            if deadline <= self.elapsed && index == LEVELS - 1 {
+                // 最高层槽位不在当前位置之后，说明属于下一轮。
+                deadline += level_range;
+            }
+            return Some((index, slot, deadline.max(self.elapsed)));
+        }
+        None
+    }
+
+    /// 返回距离下次需要处理时间轮的时长，没有定时器时返回 `None`。
+    ///
+    /// 高层槽位到期时只是下移其中的定时器，因此返回的时长可能短于实际的到期时间。
+    pub(crate) fn next_timeout(&self, now: Instant) -> Option<Duration> {
+        if !self.ready.is_empty() {
+            return Some(Duration::ZERO);
+        }
+        let (_, _, deadline) = self.next_expiration()?;
+        let deadline = self.start + Duration::from_millis(deadline);
+        Some(deadline.saturating_duration_since(now))
+    }
+
+    /// 推进到 `now` 并取出一个到期的定时器，被取出的定时器在 `collect` 时回收。
+    pub(crate) fn pop(&mut self, now: Instant) -> Option<TimerId> {
+        let now = self.tick(now);
+        loop {
+            while let Some(id) = self.ready.pop_front() {
+                if self.pending(id).is_some() {
+                    self.timers[id.index()].state = State::Fired;
+                    self.fired.push(id);
+                    return Some(id);
+                }
+            }
+            match self.next_expiration() {
+                Some((level, slot, deadline)) if deadline <= now => {
+                    self.elapsed = deadline;
+                    let level = &mut self.levels[level];
+                    level.occupied &= !(1 << slot);
+                    for id in std::mem::take(&mut level.slots[slot]) {
+                        // 未到期的下移到更低的层。
+                        if let Some(when) = self.pending(id) {
+                            self.schedule(id, when);
+                        }
+                    }
+                }
+                _ => {
+                    self.elapsed = self.elapsed.max(now);
+                    return None;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drain(wheel: &mut Wheel, now: Instant) -> Vec<TimerId> {
+        std::iter::from_fn(|| wheel.pop(now)).collect()
+    }
+
+    #[test]
+    fn test_wheel() {
+        let mut wheel = Wheel::new();
+        let start = wheel.start;
+        let at = |ms| start + Duration::from_millis(ms);
+        let a = wheel.insert(at(10));
+        let b = wheel.insert(at(100));
+        let c = wheel.insert(at(5000));
+        let d = wheel.insert(at(300_000));
+        let e = wheel.insert(at(200));
+        assert!(wheel.cancel(e));
+        assert!(!wheel.cancel(e));
+        assert_eq!(wheel.next_timeout(start), Some(Duration::from_millis(10)));
+        assert!(drain(&mut wheel, at(9)).is_empty());
+        assert_eq!(drain(&mut wheel, at(10)), vec![a]);
+        assert!(!wheel.cancel(a));
+        assert_eq!(drain(&mut wheel, at(4999)), vec![b]);
+        assert_eq!(drain(&mut wheel, at(5000)), vec![c]);
+        assert_eq!(wheel.collect(), vec![a, b, c]);
+        assert!(drain(&mut wheel, at(299_999)).is_empty());
+        assert_eq!(drain(&mut wheel, at(400_000)), vec![d]);
+        assert_eq!(wheel.next_timeout(at(400_000)), None);
+        // 回收后槽位被复用，但标识不同。
+        assert_eq!(wheel.collect(), vec![d]);
+        let f = wheel.insert(at(400_000));
+        assert_ne!(f, d);
+        assert_eq!(wheel.next_timeout(at(400_000)), Some(Duration::ZERO));
+        assert_eq!(drain(&mut wheel, at(400_000)), vec![f]);
+    }
+
+    #[test]
+    fn test_wheel_cancel() {
+        let mut wheel = Wheel::new();
+        let start = wheel.start;
+        let at = |ms| start + Duration::from_millis(ms);
+        let len = |wheel: &Wheel| {
+            let slots = wheel.levels.iter().flat_map(|x| x.slots.iter());
+            slots.map(|x| x.len()).sum::<usize>()
+        };
+        let a = wheel.insert(at(30_000));
+        let b = wheel.insert(at(30_000));
+        // 反复取消并重新添加不会使时间轮增长。
+        for _ in 0..1000 {
+            let c = wheel.insert(at(30_000));
+            assert!(wheel.cancel(c));
+        }
+        assert_eq!(len(&wheel), 2);
+        assert_eq!(wheel.timers.len(), 3);
+        // 被交换位置的定时器仍可取消及到期。
+        assert!(wheel.cancel(a));
+        assert_eq!(len(&wheel), 1);
+        assert_eq!(drain(&mut wheel, at(30_000)), vec![b]);
+        assert_eq!(len(&wheel), 0);
+        assert_eq!(wheel.next_timeout(at(30_000)), None);
+    }
+
+    #[test]
+    fn test_wheel_far() {
+        let mut wheel = Wheel::new();
+        let start = wheel.start;
+        let at = |ms| start + Duration::from_millis(ms);
+        // 跨越最高层边界及超过最高层一轮的定时器。
+        let a = wheel.insert(at(MAX_TICKS - 10));
+        let b = wheel.insert(at(MAX_TICKS * 3));
+        assert!(drain(&mut wheel, at(MAX_TICKS - 11)).is_empty());
+        assert_eq!(drain(&mut wheel, at(MAX_TICKS - 10)), vec![a]);
+        let c = wheel.insert(at(MAX_TICKS + 100));
+        assert_eq!(drain(&mut wheel, at(MAX_TICKS + 100)), vec![c]);
+        assert!(drain(&mut wheel, at(MAX_TICKS * 3 - 1)).is_empty());
+        assert_eq!(drain(&mut wheel, at(MAX_TICKS * 3)), vec![b]);
+        // 被限制到当前槽位的远期定时器不能掩盖同层中更早到期的定时器。
+        let now = at(MAX_TICKS * 3 + 1000);
+        assert!(drain(&mut wheel, now).is_empty());
+        let days = Duration::from_secs(20 * 24 * 3600);
+        let far = wheel.insert(now + Duration::from_secs(u32::MAX as u64));
+        let d = wheel.insert(now + days);
+        assert!(wheel.next_timeout(now).unwrap() <= days);
+        assert!(drain(&mut wheel, now + days - Duration::from_millis(1)).is_empty());
+        assert_eq!(drain(&mut wheel, now + days), vec![d]);
+        assert!(wheel.cancel(far));
+    }
+}